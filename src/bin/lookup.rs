@@ -4,6 +4,8 @@ use chrono::DateTime;
 use chrono::Utc;
 use clap::Parser;
 use finny::config::Config;
+use finny::diagnostics::Diagnostics;
+use finny::message::SourceKind;
 use finny::message::TextMessage;
 use finny::record::Record;
 
@@ -48,6 +50,18 @@ struct Args {
     #[clap(long, value_parser, global = true)]
     sources_fuzzy: Option<Vec<String>>,
 
+    /// Where to read messages from, overrides the source configured in the config file
+    #[clap(long, value_enum, global = true)]
+    source: Option<SourceKind>,
+
+    /// Path to a Messages app database to read instead of the one in the home directory
+    #[clap(long, value_parser, global = true)]
+    messages_db: Option<String>,
+
+    /// Path to the file to read messages from when using a file based source, `-` for stdin
+    #[clap(short, long, value_parser, global = true)]
+    input: Option<String>,
+
     /// Path to the matchers config
     #[clap(
         short,
@@ -61,12 +75,24 @@ struct Args {
 
 fn main() {
     let args = Args::parse();
-    let msgs = TextMessage::fetch(
-        &args.contacts.iter().map(|s| &s[..]).collect(),
-        &args.start,
-        &args.end,
-    )
-    .unwrap();
+    let mut config = Config::new(&args.config).expect("Error parsing configuration");
+    if let Some(kind) = args.source {
+        config.source.kind = kind;
+    }
+    if let Some(path) = args.messages_db {
+        config.source.messages_db = Some(path);
+    }
+    if let Some(path) = args.input {
+        config.source.input = Some(path);
+    }
+
+    let mut diagnostics = Diagnostics::new();
+    let msgs = config
+        .source
+        .build()
+        .expect("Error opening message source")
+        .fetch(&args.contacts, &args.start, &args.end, &mut diagnostics)
+        .unwrap();
 
     let mut msg_id_map = HashMap::new();
 
@@ -74,8 +100,13 @@ fn main() {
         msg_id_map.insert(msg.id, msg);
     }
 
-    let config = Config::new(&args.config).expect("Error parsing configuration");
-    let mut records = Record::parse_messages(&config.matchers, &msgs);
+    let mut records = Record::parse_messages(
+        &config.matchers,
+        &config.merchants,
+        &msgs,
+        false,
+        &mut diagnostics,
+    );
 
     if let Some(sources) = args.sources {
         records = finny::filter_in_sources(&records, &sources);
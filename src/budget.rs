@@ -0,0 +1,320 @@
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::exchange;
+use crate::exchange::Exchange;
+use crate::process;
+use crate::process::GroupBy;
+use crate::process::Interval;
+use crate::record::Money;
+use crate::record::Record;
+
+/// What a budget limits spending on, names are compared ignoring case
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "config")]
+pub enum Target {
+    Category(String),
+    Source(String),
+}
+
+impl Target {
+    fn matches(&self, record: &Record) -> bool {
+        let (by, name) = match self {
+            Target::Category(name) => (GroupBy::Category, name),
+            Target::Source(name) => (GroupBy::Source, name),
+        };
+        by.key(record).to_lowercase() == name.to_lowercase()
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Category(name) => write!(f, "Category: {}", name),
+            Target::Source(name) => write!(f, "Source: {}", name),
+        }
+    }
+}
+
+/// How often a budget starts over
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "config")]
+pub enum Period {
+    /// Calendar months
+    Monthly,
+    /// Weeks starting on Monday
+    Weekly,
+    /// Consecutive periods of `days` days counted from `start`, `days` must be at least 1
+    Custom { start: NaiveDate, days: u32 },
+}
+
+impl Period {
+    /// First day of the period that `date` falls in
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Monthly => Interval::Month.start_of(date),
+            Period::Weekly => Interval::Week.start_of(date),
+            Period::Custom { start, days } => {
+                let days = *days as i64;
+                let n = (date - *start).num_days().div_euclid(days);
+                *start + Duration::days(n * days)
+            }
+        }
+    }
+
+    /// First day of the period after the one starting on `start`
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Monthly => Interval::Month.next(start),
+            Period::Weekly => Interval::Week.next(start),
+            Period::Custom { days, .. } => start + Duration::days(*days as i64),
+        }
+    }
+
+    /// Periods overlapping `start` to `end` as pairs of first day and first day of the next period
+    pub fn spans(&self, start: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut spans = vec![];
+        let mut cur = self.start_of(start);
+        while cur <= end {
+            let next = self.next(cur);
+            spans.push((cur, next));
+            cur = next;
+        }
+        spans
+    }
+}
+
+/// A limit on spending per period, in the reporting currency
+#[derive(Debug, Deserialize)]
+pub struct Budget {
+    pub target: Target,
+    pub period: Period,
+    pub amount: Decimal,
+}
+
+/// Spending against a budget over a single period
+pub struct BudgetStatus {
+    pub target: String,
+    pub start: NaiveDate,
+    /// Last day of the period
+    pub end: NaiveDate,
+    pub limit: Money,
+    /// Net amount spent, refunds and other credits reduce it
+    pub spent: Money,
+}
+
+impl BudgetStatus {
+    pub fn remaining(&self) -> Money {
+        self.limit.clone() - self.spent.clone()
+    }
+
+    /// Percentage of the budget spent, `None` for a zero budget
+    pub fn percent_used(&self) -> Option<Decimal> {
+        if self.limit.is_zero() {
+            return None;
+        }
+        Some((self.spent.amount() / self.limit.amount() * Decimal::ONE_HUNDRED).round_dp(1))
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.spent > self.limit
+    }
+}
+
+/// Start of the earliest period that any budget shows for a window starting at `start`, so
+/// that the first period is complete
+pub fn window_start(budgets: &[Budget], start: &DateTime<Utc>) -> DateTime<Utc> {
+    budgets
+        .iter()
+        .map(|b| b.period.start_of(start.naive_utc().date()))
+        .min()
+        .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+        .map_or(*start, |period_start| period_start.min(*start))
+}
+
+/// Spending against each budget for every period overlapping `start` to `end`
+pub fn budget_statuses(
+    budgets: &[Budget],
+    records: &[Record],
+    exchange: &Exchange,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<Vec<BudgetStatus>, exchange::Error> {
+    let mut statuses = vec![];
+
+    for budget in budgets {
+        let matching: Vec<_> = records
+            .iter()
+            .filter(|r| budget.target.matches(r))
+            .collect();
+
+        for (from, to) in budget
+            .period
+            .spans(start.naive_utc().date(), end.naive_utc().date())
+        {
+            let in_period: Vec<_> = matching
+                .iter()
+                .copied()
+                .filter(|r| {
+                    let date = r.time.naive_utc().date();
+                    from <= date && date < to
+                })
+                .collect();
+            let total = process::calculate_total(&in_period, exchange)?;
+
+            statuses.push(BudgetStatus {
+                target: budget.target.to_string(),
+                start: from,
+                end: to - Duration::days(1),
+                limit: Money::from_decimal(budget.amount, exchange.reporting_currency()),
+                spent: Money::from_decimal(-*total.amount(), exchange.reporting_currency()),
+            });
+        }
+    }
+
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rusty_money::iso;
+
+    use super::*;
+    use crate::exchange::ExchangeConfig;
+    use crate::parser::Nature;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, month, day)
+    }
+
+    fn time(month: u32, day: u32) -> DateTime<Utc> {
+        Utc.ymd(2022, month, day).and_hms(0, 0, 0)
+    }
+
+    fn custom(start: NaiveDate, days: u32) -> Period {
+        Period::Custom { start, days }
+    }
+
+    fn budget(period: Period) -> Budget {
+        Budget {
+            target: Target::Source("Shop".to_string()),
+            period,
+            amount: Decimal::from(100),
+        }
+    }
+
+    fn record(source: &str, nature: Nature, amount: i64, month: u32, day: u32) -> Record {
+        Record {
+            matcher_id: "paid".to_string(),
+            nature,
+            message_id: day,
+            account: "1234".to_string(),
+            amount: Money::from_decimal(Decimal::from(amount), iso::PKR),
+            source: source.to_string(),
+            time: time(month, day) + Duration::hours(12),
+            category: None,
+        }
+    }
+
+    /// Start and end of each status along with what was spent
+    fn statuses(
+        budget: Budget,
+        records: &[Record],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<(NaiveDate, NaiveDate, Decimal)> {
+        let exchange = Exchange::new(&ExchangeConfig::default()).unwrap();
+        budget_statuses(&[budget], records, &exchange, &start, &end)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.start, s.end, *s.spent.amount()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_period_a_date_falls_in() {
+        assert_eq!(Period::Monthly.start_of(date(9, 24)), date(9, 1));
+        assert_eq!(Period::Weekly.start_of(date(9, 24)), date(9, 19));
+        assert_eq!(Period::Weekly.start_of(date(9, 19)), date(9, 19));
+        assert_eq!(custom(date(9, 1), 14).start_of(date(9, 24)), date(9, 15));
+        // periods are counted back from a start after the date too
+        assert_eq!(custom(date(9, 1), 14).start_of(date(8, 20)), date(8, 18));
+        assert_eq!(custom(date(9, 1), 14).start_of(date(8, 18)), date(8, 18));
+    }
+
+    #[test]
+    fn spans_every_period_overlapping_the_window() {
+        assert_eq!(
+            Period::Monthly.spans(date(9, 24), date(10, 5)),
+            [(date(9, 1), date(10, 1)), (date(10, 1), date(11, 1))]
+        );
+        assert_eq!(
+            custom(date(10, 1), 10).spans(date(9, 24), date(10, 5)),
+            [(date(9, 21), date(10, 1)), (date(10, 1), date(10, 11))]
+        );
+        assert_eq!(
+            Period::Weekly.spans(date(9, 19), date(9, 19)),
+            [(date(9, 19), date(9, 26))]
+        );
+    }
+
+    #[test]
+    fn starts_the_window_at_the_earliest_period() {
+        let start = time(9, 3) + Duration::hours(10);
+        assert_eq!(window_start(&[], &start), start);
+        assert_eq!(
+            window_start(&[budget(Period::Monthly), budget(Period::Weekly)], &start),
+            time(8, 29)
+        );
+        assert_eq!(
+            window_start(&[budget(custom(date(10, 1), 10))], &start),
+            time(9, 1)
+        );
+    }
+
+    #[test]
+    fn totals_spending_on_the_target_per_period() {
+        let records = [
+            record("SHOP", Nature::Debit, -30, 9, 5),
+            record("Other", Nature::Debit, -999, 9, 6),
+            record("Shop", Nature::Reversal, 10, 9, 10),
+            record("shop", Nature::Debit, -50, 10, 2),
+        ];
+        assert_eq!(
+            statuses(budget(Period::Monthly), &records, time(9, 24), time(10, 5)),
+            [
+                (date(9, 1), date(9, 30), Decimal::from(20)),
+                (date(10, 1), date(10, 31), Decimal::from(50)),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_periods_can_start_after_the_window() {
+        let records = [
+            record("Shop", Nature::Debit, -5, 9, 20),
+            record("Shop", Nature::Debit, -7, 9, 22),
+            record("Shop", Nature::Credit, 20, 10, 3),
+            record("Shop", Nature::Debit, -5, 10, 4),
+        ];
+        assert_eq!(
+            statuses(
+                budget(custom(date(10, 1), 10)),
+                &records,
+                time(9, 24),
+                time(10, 5)
+            ),
+            [
+                (date(9, 21), date(9, 30), Decimal::from(7)),
+                (date(10, 1), date(10, 10), Decimal::from(-15)),
+            ]
+        );
+    }
+}
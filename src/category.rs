@@ -0,0 +1,91 @@
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::record::Record;
+use crate::wrapper::Currency;
+
+/// Name of the bucket that records matching no category are reported under
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Field {
+    Source,
+    Account,
+    MatcherId,
+}
+
+impl Field {
+    fn get<'a>(&self, record: &'a Record) -> &'a str {
+        match self {
+            Field::Source => &record.source,
+            Field::Account => &record.account,
+            Field::MatcherId => &record.matcher_id,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "config")]
+pub enum Rule {
+    /// Case insensitive equality
+    Exact { field: Field, value: String },
+    /// Case insensitive substring match
+    Contains { field: Field, value: String },
+    Regex {
+        field: Field,
+        #[serde(with = "serde_regex")]
+        pattern: Regex,
+    },
+    /// Bounds (inclusive) on the absolute amount, optionally only for a single currency
+    AmountRange {
+        min: Option<Decimal>,
+        max: Option<Decimal>,
+        currency: Option<Currency>,
+    },
+    /// Matches if every one of the nested rules matches
+    All(Vec<Rule>),
+}
+
+impl Rule {
+    pub fn matches(&self, record: &Record) -> bool {
+        match self {
+            Rule::Exact { field, value } => field.get(record).eq_ignore_ascii_case(value),
+            Rule::Contains { field, value } => field
+                .get(record)
+                .to_lowercase()
+                .contains(&value.to_lowercase()),
+            Rule::Regex { field, pattern } => pattern.is_match(field.get(record)),
+            Rule::AmountRange { min, max, currency } => {
+                let amount = record.amount.amount().abs();
+                currency.iter().all(|c| c.0 == record.amount.currency())
+                    && min.iter().all(|min| amount >= *min)
+                    && max.iter().all(|max| amount <= *max)
+            }
+            Rule::All(rules) => rules.iter().all(|r| r.matches(record)),
+        }
+    }
+}
+
+/// A category is assigned to a record if any of its rules match
+#[derive(Debug, Deserialize)]
+pub struct Category {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+impl Category {
+    pub fn matches(&self, record: &Record) -> bool {
+        self.rules.iter().any(|r| r.matches(record))
+    }
+}
+
+/// Assigns each record the first of `categories` that matches it
+pub fn categorize(categories: &[Category], records: &mut [Record]) {
+    for record in records {
+        record.category = categories
+            .iter()
+            .find(|c| c.matches(record))
+            .map(|c| c.name.clone());
+    }
+}
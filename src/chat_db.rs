@@ -0,0 +1,144 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use rusqlite::params;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+
+use crate::diagnostics::Diagnostics;
+use crate::message::Error;
+use crate::message::MessageSource;
+use crate::message::TextMessage;
+use crate::typedstream;
+
+const NSECS_SINCE_2001: i64 = 978307200000000000;
+const QUERY: &str = "
+select
+	m.ROWID as id,
+	m.text as text,
+	m.date as century_epoch,
+	m.attributedBody as attributed_body,
+	h.id as sender
+from handle h
+join message m
+	on h.ROWID = m.handle_id
+where
+	h.id in rarray(?) and
+	m.date between ? and ?
+order by
+    m.date;
+";
+
+/// Reads messages from the database kept by the macOS Messages app
+pub struct ChatDb {
+    path: PathBuf,
+}
+
+impl ChatDb {
+    pub fn new(path: impl AsRef<Path>) -> ChatDb {
+        ChatDb {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Uses the live database of the current user's Messages app
+    pub fn from_home_dir() -> Result<ChatDb, Error> {
+        let mut path = match home::home_dir() {
+            Some(path) => path,
+            None => return Err(Error::HomeDirNotFound),
+        };
+        path.push("Library/Messages/chat.db");
+
+        Ok(ChatDb::new(path))
+    }
+
+    /// Opens the database read-only and marked immutable so that sqlite neither writes to it
+    /// nor takes locks that could interfere with the Messages app
+    fn open(&self) -> Result<Connection, Error> {
+        let uri = format!(
+            "file:{}?mode=ro&immutable=1",
+            ChatDb::escape_uri_path(&self.path.to_string_lossy())
+        );
+
+        Ok(Connection::open_with_flags(
+            uri,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?)
+    }
+
+    fn escape_uri_path(path: &str) -> String {
+        path.replace('%', "%25")
+            .replace('?', "%3f")
+            .replace('#', "%23")
+    }
+
+    /// Newer versions of the Messages app often leave `text` empty and only store the message
+    /// in `attributedBody`
+    fn message_text(text: Option<String>, attributed_body: Option<Vec<u8>>) -> Option<String> {
+        match text {
+            Some(text) if !text.is_empty() => Some(text),
+            _ => typedstream::decode_attributed_string(&attributed_body?),
+        }
+    }
+
+    fn parse_time_from_century_epoch(century_epoch: i64) -> DateTime<Utc> {
+        let epoch = century_epoch + NSECS_SINCE_2001;
+        Utc.timestamp(epoch / 1_000_000_000, (epoch % 1_000_000_000) as u32)
+    }
+}
+
+impl MessageSource for ChatDb {
+    fn fetch(
+        &self,
+        contacts: &[String],
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<TextMessage>, Error> {
+        let conn = self.open()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+
+        let mut stmt = conn.prepare(QUERY)?;
+        let ids: Rc<Vec<_>> = Rc::new(contacts.iter().cloned().map(Value::from).collect());
+
+        let msgs = stmt.query_map(
+            params![
+                ids,
+                start.timestamp_nanos() - NSECS_SINCE_2001,
+                (end.timestamp_nanos() - NSECS_SINCE_2001),
+            ],
+            |row| {
+                let id: u32 = row.get(0)?;
+                let time = ChatDb::parse_time_from_century_epoch(row.get(2)?);
+                let text = ChatDb::message_text(row.get(1)?, row.get(3)?);
+                Ok((id, row.get(4)?, text, time))
+            },
+        )?;
+
+        let mut readable = vec![];
+        for msg in msgs {
+            match msg? {
+                (id, sender, Some(text), time) => readable.push(TextMessage {
+                    id,
+                    sender,
+                    text,
+                    time,
+                }),
+                (id, _, None, time) => diagnostics.push_unreadable(
+                    id,
+                    Some(time),
+                    "it has no readable text".to_string(),
+                ),
+            }
+        }
+
+        Ok(readable)
+    }
+}
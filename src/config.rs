@@ -7,13 +7,23 @@ use strum_macros::Display;
 use yaml2json_rs::Style;
 use yaml2json_rs::Yaml2Json;
 
+use crate::budget::Budget;
+use crate::category::Category;
+use crate::exchange::ExchangeConfig;
+use crate::merchant::MerchantConfig;
+use crate::message::SourceConfig;
 use crate::parser::Matcher;
+use crate::validate;
+use crate::validate::Problem;
 
 #[derive(Debug, Display)]
 pub enum Error {
     FileReadFailure(io::Error),
     YamlConversionFailure(yaml2json_rs::Yaml2JsonError),
     DeError(serde_json::error::Error),
+    /// The config doesn't match what's expected, or has mistakes like capture groups missing
+    /// from patterns
+    Invalid(Vec<Problem>),
 }
 
 impl error::Error for Error {}
@@ -38,14 +48,32 @@ impl From<serde_json::error::Error> for Error {
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    #[serde(default)]
+    pub source: SourceConfig,
+    /// Path to the ledger database that synced transactions are stored in
+    pub ledger: Option<String>,
+    /// Reporting currency and the exchange rates used to convert amounts into it
+    #[serde(default)]
+    pub currency: ExchangeConfig,
     pub matchers: Vec<Matcher>,
+    /// Rules assigning categories to records, the first matching category is used
+    #[serde(default)]
+    pub categories: Vec<Category>,
+    /// Aliases and cleanup applied to merchant names while parsing
+    #[serde(default)]
+    pub merchants: MerchantConfig,
+    /// Spending limits per category or source, see `finny budget`
+    #[serde(default)]
+    pub budgets: Vec<Budget>,
 }
 
 impl Config {
     pub fn new(path: &str) -> Result<Config, Error> {
-        let cfg_str = fs::read_to_string(path)?;
+        let yaml = fs::read_to_string(path)?;
         let converter = Yaml2Json::new(Style::PRETTY);
-        let cfg_str = converter.document_to_string(&cfg_str)?;
-        Ok(serde_json::from_str(&cfg_str)?)
+        let cfg_str = converter.document_to_string(&yaml)?;
+        let value: serde_json::Value = serde_json::from_str(&cfg_str)?;
+
+        validate::load(&yaml, value).map_err(Error::Invalid)
     }
 }
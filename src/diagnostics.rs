@@ -0,0 +1,98 @@
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::message::TextMessage;
+
+/// Why a message didn't turn into a record, or why its record is suspect
+#[derive(Debug, Clone)]
+pub enum Problem {
+    /// The message couldn't be read from its source and was skipped
+    Unreadable { reason: String },
+    /// No matcher's pattern matched the message
+    Unmatched,
+    /// A matcher's pattern matched but one of the values couldn't be read
+    Failed {
+        matcher_id: String,
+        field: String,
+        error: String,
+    },
+    /// The time read from the message is far from when it was received, the record is kept
+    ImplausibleTime {
+        matcher_id: String,
+        time: DateTime<Utc>,
+    },
+    /// Other matchers with the same or a lower priority matched the message too, the record
+    /// from `matcher_id` is kept
+    Overlap {
+        matcher_id: String,
+        others: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message_id: u32,
+    /// Unknown for some unreadable messages
+    pub received: Option<DateTime<Utc>>,
+    pub text: String,
+    pub problem: Problem,
+}
+
+/// Collects problems found while parsing messages, so they can be reported apart from the records
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn push(&mut self, msg: &TextMessage, problem: Problem) {
+        self.entries.push(Diagnostic {
+            message_id: msg.id,
+            received: Some(msg.time),
+            text: msg.text.clone(),
+            problem,
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.entries.iter()
+    }
+
+    /// Records a message that was skipped because it couldn't be read from its source
+    pub fn push_unreadable(
+        &mut self,
+        message_id: u32,
+        received: Option<DateTime<Utc>>,
+        reason: String,
+    ) {
+        self.entries.push(Diagnostic {
+            message_id,
+            received,
+            text: String::new(),
+            problem: Problem::Unreadable { reason },
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of unreadable messages, unmatched messages, failed messages and records with
+    /// implausible times, overlaps aren't counted as they still produce a record
+    pub fn counts(&self) -> (usize, usize, usize, usize) {
+        self.entries.iter().fold(
+            (0, 0, 0, 0),
+            |(unreadable, unmatched, failed, times), d| match d.problem {
+                Problem::Unreadable { .. } => (unreadable + 1, unmatched, failed, times),
+                Problem::Unmatched => (unreadable, unmatched + 1, failed, times),
+                Problem::Failed { .. } => (unreadable, unmatched, failed + 1, times),
+                Problem::ImplausibleTime { .. } => (unreadable, unmatched, failed, times + 1),
+                Problem::Overlap { .. } => (unreadable, unmatched, failed, times),
+            },
+        )
+    }
+}
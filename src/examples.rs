@@ -0,0 +1,294 @@
+use crate::diagnostics::Diagnostics;
+use crate::diagnostics::Problem;
+use crate::merchant::MerchantConfig;
+use crate::message::TextMessage;
+use crate::parser::Example;
+use crate::parser::Matcher;
+use crate::parser::RecordParser;
+use crate::record::Record;
+
+/// A value read from an example that differs from the expected one
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+pub struct ExampleResult {
+    pub matcher_id: String,
+    /// Position of the example within its matcher, starting at 1
+    pub number: usize,
+    pub text: String,
+    pub mismatches: Vec<Mismatch>,
+    /// Other matchers whose patterns matched the example too, which only priority or the order
+    /// of the matchers kept from being used
+    pub overlaps: Vec<String>,
+}
+
+impl ExampleResult {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Parses the examples of every matcher with all of `matchers`, so that an example picked up
+/// by the wrong matcher fails too and one matched by several is reported
+pub fn run_examples(matchers: &[Matcher], merchants: &MerchantConfig) -> Vec<ExampleResult> {
+    let parser = RecordParser::new(matchers, merchants, true);
+
+    matchers
+        .iter()
+        .flat_map(|matcher| {
+            matcher
+                .examples
+                .iter()
+                .enumerate()
+                .map(move |(i, example)| (matcher, i + 1, example))
+        })
+        .map(|(matcher, number, example)| run_example(&parser, matcher, number, example))
+        .collect()
+}
+
+fn run_example(
+    parser: &RecordParser,
+    matcher: &Matcher,
+    number: usize,
+    example: &Example,
+) -> ExampleResult {
+    let mut result = ExampleResult {
+        matcher_id: matcher.id.clone(),
+        number,
+        text: example.text.clone(),
+        mismatches: vec![],
+        overlaps: vec![],
+    };
+
+    // examples without either are rejected when the config is loaded
+    let time = match example.received.or(example.time) {
+        Some(time) => time,
+        None => {
+            result.mismatches.push(Mismatch {
+                field: "received".to_string(),
+                expected: "the time the message was received".to_string(),
+                actual: "nothing".to_string(),
+            });
+            return result;
+        }
+    };
+
+    let msg = TextMessage {
+        id: number as u32,
+        sender: example
+            .sender
+            .clone()
+            .or_else(|| matcher.senders.first().cloned())
+            .unwrap_or_default(),
+        text: example.text.clone(),
+        time,
+    };
+    let mut diagnostics = Diagnostics::new();
+    result.mismatches = match parser.parse(&msg, &mut diagnostics) {
+        Some(record) => compare(matcher, example, &record),
+        None => vec![Mismatch {
+            field: "record".to_string(),
+            expected: format!("a record from {}", matcher.id),
+            actual: describe_failure(&diagnostics),
+        }],
+    };
+    result.overlaps = diagnostics
+        .iter()
+        .find_map(|d| match &d.problem {
+            Problem::Overlap { others, .. } => Some(others.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    result
+}
+
+fn describe_failure(diagnostics: &Diagnostics) -> String {
+    let failure = diagnostics
+        .iter()
+        .map(|d| &d.problem)
+        .find(|p| !matches!(p, Problem::Overlap { .. }));
+    match failure {
+        Some(Problem::Unmatched) => "no pattern matched".to_string(),
+        Some(Problem::Failed {
+            matcher_id,
+            field,
+            error,
+        }) => format!("{} failed to read {}: {}", matcher_id, field, error),
+        _ => "nothing".to_string(),
+    }
+}
+
+fn compare(matcher: &Matcher, example: &Example, record: &Record) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    let mut check = |field: &str, expected: Option<String>, actual: String| {
+        if let Some(expected) = expected {
+            if expected != actual {
+                mismatches.push(Mismatch {
+                    field: field.to_string(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+    };
+
+    check(
+        "matcher",
+        Some(matcher.id.clone()),
+        record.matcher_id.clone(),
+    );
+    check(
+        "nature",
+        example.nature.map(|n| n.to_string()),
+        record.nature.to_string(),
+    );
+    check("account", example.account.clone(), record.account.clone());
+    check(
+        "amount",
+        example.amount.map(|a| a.normalize().to_string()),
+        record.amount.amount().abs().normalize().to_string(),
+    );
+    check(
+        "currency",
+        example
+            .currency
+            .as_ref()
+            .map(|c| c.iso_alpha_code.to_string()),
+        record.amount.currency().iso_alpha_code.to_string(),
+    );
+    check("source", example.source.clone(), record.source.clone());
+    check(
+        "time",
+        example.time.map(|t| t.to_rfc3339()),
+        record.time.to_rfc3339(),
+    );
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(id: &str, pattern: &str, priority: i32, examples: serde_json::Value) -> Matcher {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "pattern": pattern,
+            "priority": priority,
+            "nature": "Debit",
+            "values": {
+                "account": {"type": "Fixed", "config": "1234"},
+                "amount": {"type": "FromMatch", "config": {"group": "amount", "parser": null}},
+                "currency": {"type": "Fixed", "config": "PKR"},
+                "source": {"type": "Fixed", "config": "Shop"},
+                "time": {"type": "MessageTime"},
+            },
+            "examples": examples,
+        }))
+        .unwrap()
+    }
+
+    fn example(text: &str, expected: serde_json::Value) -> serde_json::Value {
+        let mut example = serde_json::json!({
+            "text": text,
+            "received": "2022-09-24T10:00:00Z",
+        });
+        example
+            .as_object_mut()
+            .unwrap()
+            .extend(expected.as_object().unwrap().clone());
+        example
+    }
+
+    fn results(matchers: &[Matcher]) -> Vec<ExampleResult> {
+        run_examples(matchers, &MerchantConfig::default())
+    }
+
+    fn mismatches(result: &ExampleResult) -> Vec<(&str, &str, &str)> {
+        result
+            .mismatches
+            .iter()
+            .map(|m| (m.field.as_str(), m.expected.as_str(), m.actual.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn passes_examples_read_as_expected() {
+        let expected = serde_json::json!({
+            "nature": "Debit",
+            "account": "1234",
+            "amount": 12.5,
+            "currency": "PKR",
+            "source": "Shop",
+            "time": "2022-09-24T10:00:00Z",
+        });
+        let matchers = [matcher(
+            "paid",
+            r"Paid (?P<amount>[\d.]+)",
+            0,
+            serde_json::json!([example("Paid 12.50", expected)]),
+        )];
+
+        let results = results(&matchers);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].passed());
+        assert_eq!(
+            (results[0].matcher_id.as_str(), results[0].number),
+            ("paid", 1)
+        );
+        assert!(results[0].overlaps.is_empty());
+    }
+
+    #[test]
+    fn reports_values_that_differ() {
+        let expected = serde_json::json!({"account": "9999", "amount": 12.5});
+        let matchers = [matcher(
+            "paid",
+            r"Paid (?P<amount>[\d.]+)",
+            0,
+            serde_json::json!([example("Paid 12.50", expected)]),
+        )];
+
+        let results = results(&matchers);
+        assert!(!results[0].passed());
+        assert_eq!(mismatches(&results[0]), [("account", "9999", "1234")]);
+    }
+
+    #[test]
+    fn fails_examples_picked_up_by_another_matcher() {
+        let matchers = [
+            matcher(
+                "broad",
+                r"(?P<amount>[\d.]+)",
+                0,
+                serde_json::json!([example("Paid 5", serde_json::json!({}))]),
+            ),
+            matcher("paid", r"Paid (?P<amount>[\d.]+)", 1, serde_json::json!([])),
+        ];
+
+        let results = results(&matchers);
+        assert_eq!(mismatches(&results[0]), [("matcher", "broad", "paid")]);
+        assert_eq!(results[0].overlaps, ["broad"]);
+    }
+
+    #[test]
+    fn fails_examples_no_matcher_matches() {
+        let matchers = [matcher(
+            "paid",
+            r"Paid (?P<amount>[\d.]+)",
+            0,
+            serde_json::json!([example("Received 5", serde_json::json!({}))]),
+        )];
+
+        let results = results(&matchers);
+        assert_eq!(
+            mismatches(&results[0]),
+            [("record", "a record from paid", "no pattern matched")]
+        );
+        assert!(results[0].overlaps.is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use rusty_money::iso;
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use crate::record::Money;
+use crate::wrapper::Currency;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Pairs of ISO codes that no rate could be found or derived for
+    MissingRates(Vec<(String, String)>),
+    RatesFileError(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingRates(missing) => {
+                let pairs: Vec<_> = missing
+                    .iter()
+                    .map(|(from, to)| format!("{} -> {}", from, to))
+                    .collect();
+                write!(f, "exchange rates not configured: {}", pairs.join(", "))
+            }
+            Error::RatesFileError(err) => write!(f, "error reading rates file: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::RatesFileError(error.to_string())
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Self {
+        Error::RatesFileError(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::RatesFileError(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rate {
+    pub from: Currency,
+    pub to: Currency,
+    #[serde(deserialize_with = "positive_rate")]
+    pub rate: Decimal,
+}
+
+fn positive_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    let rate = <Decimal as Deserialize>::deserialize(deserializer)?;
+    if rate.is_sign_positive() && !rate.is_zero() {
+        Ok(rate)
+    } else {
+        Err(D::Error::custom(format!("rate must be positive: {}", rate)))
+    }
+}
+
+/// Parses rates written as `FROM/TO=RATE`, e.g. `USD/PKR=237`
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rate, expected FROM/TO=RATE: {}", s);
+        let (pair, rate) = s.split_once('=').ok_or_else(invalid)?;
+        let (from, to) = pair.split_once('/').ok_or_else(invalid)?;
+        let rate: Decimal = rate.trim().parse().map_err(|_| invalid())?;
+        if rate.is_sign_negative() || rate.is_zero() {
+            return Err(format!("rate must be positive: {}", s));
+        }
+
+        Ok(Rate {
+            from: from.trim().parse()?,
+            to: to.trim().parse()?,
+            rate,
+        })
+    }
+}
+
+/// A rate effective from `date` until the next rate for the same pair
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoricalRate {
+    pub date: NaiveDate,
+    pub from: Currency,
+    pub to: Currency,
+    #[serde(deserialize_with = "positive_rate")]
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExchangeConfig {
+    /// Currency that amounts are converted to for reporting
+    #[serde(default = "default_reporting_currency")]
+    pub reporting: Currency,
+    /// Currency through which cross rates are derived for pairs without a configured rate
+    pub base: Option<Currency>,
+    #[serde(default)]
+    pub rates: Vec<Rate>,
+    /// Path to a file of daily rates, either a CSV with `date,from,to,rate` columns or a JSON
+    /// array of objects with the same fields. These take precedence over `rates`
+    pub history: Option<String>,
+}
+
+fn default_reporting_currency() -> Currency {
+    Currency(iso::PKR)
+}
+
+impl Default for ExchangeConfig {
+    fn default() -> Self {
+        ExchangeConfig {
+            reporting: default_reporting_currency(),
+            base: None,
+            rates: vec![],
+            history: None,
+        }
+    }
+}
+
+/// An amount converted into the reporting currency along with the rate that was used
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub amount: Money,
+    pub rate: Decimal,
+    /// Date of the historical rate used, `None` if a fixed rate was used
+    pub rate_date: Option<NaiveDate>,
+}
+
+type Pair = (&'static str, &'static str);
+
+/// Converts amounts into the reporting currency using configured rates, their inverses and
+/// cross rates through the base currency
+pub struct Exchange {
+    reporting: &'static iso::Currency,
+    base: Option<&'static iso::Currency>,
+    rates: HashMap<Pair, Decimal>,
+    history: HashMap<Pair, BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl Exchange {
+    pub fn new(config: &ExchangeConfig) -> Result<Exchange, Error> {
+        let mut history: HashMap<Pair, BTreeMap<NaiveDate, Decimal>> = HashMap::new();
+        if let Some(path) = &config.history {
+            for r in Exchange::read_history(path)? {
+                history
+                    .entry((r.from.iso_alpha_code, r.to.iso_alpha_code))
+                    .or_default()
+                    .insert(r.date, r.rate);
+            }
+        }
+
+        Ok(Exchange {
+            reporting: config.reporting.0,
+            base: config.base.as_ref().map(|c| c.0),
+            rates: config
+                .rates
+                .iter()
+                .map(|r| ((r.from.iso_alpha_code, r.to.iso_alpha_code), r.rate))
+                .collect(),
+            history,
+        })
+    }
+
+    fn read_history(path: &str) -> Result<Vec<HistoricalRate>, Error> {
+        let file = File::open(path)?;
+
+        if path.ends_with(".json") {
+            Ok(serde_json::from_reader(io::BufReader::new(file))?)
+        } else {
+            Ok(csv::Reader::from_reader(file)
+                .deserialize()
+                .collect::<Result<_, _>>()?)
+        }
+    }
+
+    pub fn reporting_currency(&self) -> &'static iso::Currency {
+        self.reporting
+    }
+
+    /// The rate effective on `date` along with the date of the historical rate it came from
+    pub fn rate(
+        &self,
+        from: &iso::Currency,
+        to: &iso::Currency,
+        date: &NaiveDate,
+    ) -> Option<(Decimal, Option<NaiveDate>)> {
+        self.direct_rate(from, to, date).or_else(|| {
+            let base = self.base?;
+            let (first, first_date) = self.direct_rate(from, base, date)?;
+            let (second, second_date) = self.direct_rate(base, to, date)?;
+            // the older of the historical rates used, if any
+            let rate_date = match (first_date, second_date) {
+                (Some(first), Some(second)) => Some(first.min(second)),
+                (first, second) => first.or(second),
+            };
+            Some((first.checked_mul(second)?, rate_date))
+        })
+    }
+
+    fn direct_rate(
+        &self,
+        from: &iso::Currency,
+        to: &iso::Currency,
+        date: &NaiveDate,
+    ) -> Option<(Decimal, Option<NaiveDate>)> {
+        if from == to {
+            return Some((Decimal::ONE, None));
+        }
+
+        let (from, to) = (from.iso_alpha_code, to.iso_alpha_code);
+        // rates are checked to be positive when read, a rate that still can't be inverted is
+        // treated as missing
+        let invert = |(rate, date): (Decimal, Option<NaiveDate>)| {
+            Some((Decimal::ONE.checked_div(rate)?, date))
+        };
+
+        self.historical_rate((from, to), date)
+            .or_else(|| self.historical_rate((to, from), date).and_then(invert))
+            .or_else(|| self.rates.get(&(from, to)).map(|r| (*r, None)))
+            .or_else(|| self.rates.get(&(to, from)).and_then(|r| invert((*r, None))))
+    }
+
+    /// The latest rate on or before `date`
+    fn historical_rate(
+        &self,
+        pair: Pair,
+        date: &NaiveDate,
+    ) -> Option<(Decimal, Option<NaiveDate>)> {
+        self.history
+            .get(&pair)?
+            .range(..=date)
+            .next_back()
+            .map(|(d, r)| (*r, Some(*d)))
+    }
+
+    /// Converts `amount` into the reporting currency at the rate effective at `time`
+    pub fn convert(&self, amount: &Money, time: &DateTime<Utc>) -> Result<Conversion, Error> {
+        let (rate, rate_date) = self
+            .rate(amount.currency(), self.reporting, &time.naive_utc().date())
+            .ok_or_else(|| {
+                Error::MissingRates(vec![(
+                    amount.currency().iso_alpha_code.to_string(),
+                    self.reporting.iso_alpha_code.to_string(),
+                )])
+            })?;
+
+        Ok(Conversion {
+            amount: Money::from_decimal(amount.amount() * rate, self.reporting),
+            rate,
+            rate_date,
+        })
+    }
+
+    /// Makes sure every one of `amounts` can be converted, reporting all missing pairs at once
+    pub fn check<'a>(
+        &self,
+        amounts: impl IntoIterator<Item = (&'a Money, &'a DateTime<Utc>)>,
+    ) -> Result<(), Error> {
+        let mut missing: Vec<(String, String)> = vec![];
+
+        for (amount, time) in amounts {
+            if let Err(Error::MissingRates(pairs)) = self.convert(amount, time) {
+                for pair in pairs {
+                    if !missing.contains(&pair) {
+                        missing.push(pair);
+                    }
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MissingRates(missing))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rates: &[&str]) -> ExchangeConfig {
+        ExchangeConfig {
+            reporting: Currency(iso::PKR),
+            base: Some(Currency(iso::USD)),
+            rates: rates.iter().map(|r| r.parse().unwrap()).collect(),
+            history: None,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2026, 10, day)
+    }
+
+    #[test]
+    fn rejects_rates_that_are_not_positive() {
+        assert!("USD/PKR=0".parse::<Rate>().is_err());
+        assert!("USD/PKR=-280".parse::<Rate>().is_err());
+        assert!(
+            serde_json::from_str::<Rate>(r#"{"from": "USD", "to": "PKR", "rate": 0}"#).is_err()
+        );
+        assert!(serde_json::from_str::<HistoricalRate>(
+            r#"{"date": "2026-10-01", "from": "USD", "to": "PKR", "rate": "-1"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cross_rates_keep_the_date_of_historical_legs() {
+        let mut exchange = Exchange::new(&config(&["EUR/USD=1.1"])).unwrap();
+        exchange.history.insert(
+            ("USD", "PKR"),
+            BTreeMap::from([(date(1), Decimal::from(280)), (date(5), Decimal::from(282))]),
+        );
+
+        assert_eq!(
+            exchange.rate(iso::EUR, iso::PKR, &date(7)),
+            Some((Decimal::new(3102, 1), Some(date(5))))
+        );
+
+        exchange.history.insert(
+            ("EUR", "USD"),
+            BTreeMap::from([(date(3), Decimal::new(12, 1))]),
+        );
+        assert_eq!(
+            exchange.rate(iso::EUR, iso::PKR, &date(7)),
+            Some((Decimal::new(3384, 1), Some(date(3))))
+        );
+    }
+
+    #[test]
+    fn inverts_rates() {
+        let exchange = Exchange::new(&config(&["USD/PKR=250"])).unwrap();
+        assert_eq!(
+            exchange.rate(iso::PKR, iso::USD, &date(1)),
+            Some((Decimal::new(4, 3), None))
+        );
+    }
+}
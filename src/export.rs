@@ -0,0 +1,260 @@
+use std::io::BufRead;
+
+use chrono::DateTime;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::diagnostics::Diagnostics;
+use crate::message;
+use crate::message::open_input;
+use crate::message::Error;
+use crate::message::MessageSource;
+use crate::message::TextMessage;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+/// Names of the CSV columns or JSON fields holding each part of a message
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FieldMapping {
+    pub sender: String,
+    pub body: String,
+    pub time: String,
+    /// strftime format of the timestamp, RFC 3339 when not set. Timestamps without an offset
+    /// are read as UTC
+    pub time_format: Option<String>,
+    /// Field holding the message id, messages are identified by a hash of their sender, time
+    /// and body when not set
+    pub id: Option<String>,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        FieldMapping {
+            sender: "sender".to_string(),
+            body: "body".to_string(),
+            time: "time".to_string(),
+            time_format: None,
+            id: None,
+        }
+    }
+}
+
+/// Reads messages exported by other tools as CSV (with a header row) or JSON Lines
+pub struct Export {
+    path: String,
+    format: ExportFormat,
+    mapping: FieldMapping,
+}
+
+/// The fields of a row that make up a message, any of which may be missing
+struct Row {
+    /// Line of the input the row starts on
+    line: u32,
+    id: Option<String>,
+    sender: Option<String>,
+    body: Option<String>,
+    time: Option<String>,
+}
+
+impl Export {
+    /// `path` may be `-` to read from stdin
+    pub fn new(path: &str, format: ExportFormat, mapping: FieldMapping) -> Export {
+        Export {
+            path: path.to_string(),
+            format,
+            mapping,
+        }
+    }
+
+    fn row(&self, line: u32, field: Field) -> Row {
+        Row {
+            line,
+            id: self.mapping.id.as_deref().and_then(field),
+            sender: field(&self.mapping.sender),
+            body: field(&self.mapping.body),
+            time: field(&self.mapping.time),
+        }
+    }
+
+    /// Reads the rows of a CSV file, passing rows that can't be read on as errors
+    fn read_csv(
+        &self,
+        input: Box<dyn BufRead>,
+        mut visit: impl FnMut(u32, Result<Row, String>),
+    ) -> Result<(), Error> {
+        let mut reader = csv::Reader::from_reader(input);
+        let headers = reader.headers()?.clone();
+
+        for (i, row) in reader.records().enumerate() {
+            // the header is on the first line
+            let line = i as u32 + 2;
+            match row {
+                Ok(row) => {
+                    let line = row.position().map_or(line, |p| p.line() as u32);
+                    visit(
+                        line,
+                        Ok(self.row(line, &|name| {
+                            headers
+                                .iter()
+                                .position(|h| h == name)
+                                .and_then(|pos| row.get(pos))
+                                .map(ToString::to_string)
+                        })),
+                    );
+                }
+                Err(err) if err.is_io_error() => return Err(err.into()),
+                Err(err) => {
+                    let line = err.position().map_or(line, |p| p.line() as u32);
+                    visit(line, Err(err.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the objects of a JSON Lines file, passing lines that can't be read on as errors
+    fn read_json_lines(
+        &self,
+        input: Box<dyn BufRead>,
+        mut visit: impl FnMut(u32, Result<Row, String>),
+    ) -> Result<(), Error> {
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            let number = i as u32 + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Map<String, Value>>(&line) {
+                Ok(object) => visit(
+                    number,
+                    Ok(self.row(number, &|name| match object.get(name)? {
+                        Value::String(s) => Some(s.clone()),
+                        Value::Null => None,
+                        other => Some(other.to_string()),
+                    })),
+                ),
+                Err(err) => visit(
+                    number,
+                    Err(format!("line {}: invalid JSON: {}", number, err)),
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The configured id of `row`, or a hash of its sender, time and body so that ids don't
+    /// change when rows are added to or removed from an export
+    fn id(&self, row: &Row) -> Result<u32, String> {
+        match (&self.mapping.id, &row.id) {
+            (Some(name), None) => Err(format!("no field {}", name)),
+            (Some(_), Some(id)) => id
+                .parse()
+                .map_err(|_| format!("invalid message id: {}", id)),
+            (None, _) => Ok(message::stable_id(&[
+                row.sender.as_deref().unwrap_or_default(),
+                row.time.as_deref().unwrap_or_default(),
+                row.body.as_deref().unwrap_or_default(),
+            ])),
+        }
+    }
+
+    fn time(&self, row: &Row) -> Result<DateTime<Utc>, String> {
+        let val = row
+            .time
+            .as_deref()
+            .ok_or_else(|| format!("no field {}", self.mapping.time))?;
+        let time = match &self.mapping.time_format {
+            None => DateTime::parse_from_rfc3339(val).map(|t| t.with_timezone(&Utc)),
+            Some(format) => DateTime::parse_from_str(val, format)
+                .map(|t| t.with_timezone(&Utc))
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(val, format).map(|t| DateTime::from_utc(t, Utc))
+                }),
+        };
+
+        time.map_err(|e| format!("invalid message time {}: {}", val, e))
+    }
+
+    /// Whether the row is from one of `contacts` and between `start` and `end`, as far as its
+    /// fields tell
+    fn is_wanted(
+        &self,
+        row: &Row,
+        contacts: &[String],
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> bool {
+        let from_contact = match &row.sender {
+            Some(sender) => contacts.contains(sender),
+            None => true,
+        };
+        let in_window = match self.time(row) {
+            Ok(time) => &time >= start && &time <= end,
+            Err(_) => true,
+        };
+
+        from_contact && in_window
+    }
+
+    fn to_message(&self, row: &Row) -> Result<TextMessage, String> {
+        let required = |value: &Option<String>, name: &str| {
+            value.clone().ok_or_else(|| format!("no field {}", name))
+        };
+
+        Ok(TextMessage {
+            id: self.id(row)?,
+            sender: required(&row.sender, &self.mapping.sender)?,
+            text: required(&row.body, &self.mapping.body)?,
+            time: self.time(row)?,
+        })
+    }
+}
+
+/// Looks up the value of a column or field of the current row by name
+type Field<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+impl MessageSource for Export {
+    fn fetch(
+        &self,
+        contacts: &[String],
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<TextMessage>, Error> {
+        let input = open_input(&self.path)?;
+        let mut msgs = Vec::new();
+
+        // rows that can't be read at all are identified by their line, which the reason includes
+        let visit = |line, row: Result<Row, String>| match row {
+            Ok(row) if !self.is_wanted(&row, contacts, start, end) => {}
+            Ok(row) => match self.to_message(&row) {
+                Ok(msg) => msgs.push(msg),
+                Err(reason) => diagnostics.push_unreadable(
+                    self.id(&row).unwrap_or(row.line),
+                    self.time(&row).ok(),
+                    format!("line {}: {}", row.line, reason),
+                ),
+            },
+            Err(reason) => diagnostics.push_unreadable(line, None, reason),
+        };
+
+        match self.format {
+            ExportFormat::Csv => self.read_csv(input, visit)?,
+            ExportFormat::JsonLines => self.read_json_lines(input, visit)?,
+        }
+
+        msgs.sort_by_key(|m| m.time);
+        Ok(msgs)
+    }
+}
@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+use std::error;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::SecondsFormat;
+use chrono::Utc;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rust_decimal::Decimal;
+use rusty_money::iso;
+use strum_macros::Display;
+
+use crate::diagnostics::Diagnostics;
+use crate::diagnostics::Problem;
+use crate::message::TextMessage;
+use crate::parser::Nature;
+use crate::record::Money;
+use crate::record::Record;
+
+/// Brings the schema from one version to the next, the version a ledger is at is kept in
+/// `pragma user_version`
+type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+const MIGRATIONS: &[Migration] = &[
+    Ledger::create_records,
+    Ledger::add_nature,
+    Ledger::add_sync_progress,
+    Ledger::key_by_message_source,
+];
+
+#[derive(Debug, Display)]
+pub enum Error {
+    SqliteError(rusqlite::Error),
+    InvalidRecord(String),
+}
+
+impl error::Error for Error {}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Error {
+        Error::SqliteError(error)
+    }
+}
+
+/// finny's own database of parsed transactions, kept separately from any message source so
+/// that history survives changes to where messages come from
+pub struct Ledger {
+    conn: Connection,
+}
+
+impl Ledger {
+    pub fn open(path: &str) -> Result<Ledger, Error> {
+        let mut conn = Connection::open(path)?;
+        Ledger::migrate(&mut conn)?;
+        Ok(Ledger { conn })
+    }
+
+    fn migrate(conn: &mut Connection) -> Result<(), Error> {
+        let version: usize = conn.query_row("pragma user_version", [], |row| row.get(0))?;
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = conn.transaction()?;
+            migration(&tx)?;
+            tx.execute_batch(&format!("pragma user_version = {}", i + 1))?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    fn create_records(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "create table if not exists records (
+                id integer primary key,
+                message_id integer not null,
+                matcher_id text not null,
+                account text not null,
+                amount text not null,
+                currency text not null,
+                source text not null,
+                time text not null,
+                parsed_at text not null,
+                unique (message_id, time)
+            );
+
+            create table if not exists syncs (
+                id integer primary key,
+                synced_at text not null,
+                last_message_time text not null
+            );",
+        )
+    }
+
+    /// Records synced before natures were stored are credits or debits by the sign of their
+    /// amount. Ledgers made before versions were tracked may have the column already
+    fn add_nature(conn: &Connection) -> rusqlite::Result<()> {
+        let has_nature = conn
+            .prepare("select 1 from pragma_table_info('records') where name = 'nature'")?
+            .exists([])?;
+        if has_nature {
+            return Ok(());
+        }
+
+        conn.execute_batch(
+            "alter table records add column nature text not null default 'Debit';
+            update records set nature = 'Credit' where amount not like '-%';",
+        )
+    }
+
+    /// Replaces the single sync time with one per source and contact, which are synced from
+    /// the start again as it isn't known which the old time applied to
+    fn add_sync_progress(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "create table if not exists sync_progress (
+                source text not null,
+                contact text not null,
+                synced_until text not null,
+                synced_at text not null,
+                primary key (source, contact)
+            );
+
+            drop table if exists syncs;",
+        )
+    }
+
+    /// Records were unique by message id and parsed time, so parsing a message again with a
+    /// different time added it twice. They are now unique by the message source and id, the
+    /// latest copy of each is kept. Earlier records are assigned the only source synced so far,
+    /// if there is just one
+    fn key_by_message_source(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "create table records_by_source (
+                id integer primary key,
+                message_source text not null,
+                message_id integer not null,
+                matcher_id text not null,
+                nature text not null,
+                account text not null,
+                amount text not null,
+                currency text not null,
+                source text not null,
+                time text not null,
+                parsed_at text not null,
+                unique (message_source, message_id)
+            );
+
+            insert or replace into records_by_source
+                (message_source, message_id, matcher_id, nature, account, amount, currency,
+                source, time, parsed_at)
+            select
+                (select case when count(distinct source) = 1 then min(source) else '' end
+                    from sync_progress),
+                message_id, matcher_id, nature, account, amount, currency, source, time,
+                parsed_at
+            from records
+            order by id;
+
+            drop table records;
+            alter table records_by_source rename to records;",
+        )
+    }
+
+    /// The time after which messages from `contact` in `source` haven't been synced yet, `None`
+    /// if nothing was synced
+    pub fn synced_until(
+        &self,
+        source: &str,
+        contact: &str,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let time: Option<String> = self
+            .conn
+            .query_row(
+                "select synced_until from sync_progress where source = ? and contact = ?",
+                params![source, contact],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        time.map(|t| Ledger::parse_time(&t)).transpose()
+    }
+
+    /// The start of the window of messages from each of `contacts` in `source` that still need
+    /// to be synced, `default` for contacts that weren't synced yet
+    pub fn sync_starts(
+        &self,
+        source: &str,
+        contacts: &[String],
+        default: &DateTime<Utc>,
+    ) -> Result<HashMap<String, DateTime<Utc>>, Error> {
+        contacts
+            .iter()
+            .map(|contact| {
+                let start = self
+                    .synced_until(source, contact)?
+                    .map(|t| t + Duration::nanoseconds(1))
+                    .unwrap_or(*default);
+                Ok((contact.clone(), start))
+            })
+            .collect()
+    }
+
+    /// How far the messages of each contact can be marked as synced: up to the latest of
+    /// `msgs`, but not past one that failed to parse so that it is read again once the config
+    /// is fixed
+    pub fn sync_progress(
+        msgs: &[TextMessage],
+        diagnostics: &Diagnostics,
+    ) -> HashMap<String, DateTime<Utc>> {
+        let mut progress: HashMap<String, DateTime<Utc>> = HashMap::new();
+        for msg in msgs {
+            let until = progress.entry(msg.sender.clone()).or_insert(msg.time);
+            *until = (*until).max(msg.time);
+        }
+
+        let senders: HashMap<_, _> = msgs.iter().map(|m| (m.id, &m.sender)).collect();
+        for d in diagnostics.iter() {
+            if let (Problem::Failed { .. }, Some(sender), Some(received)) =
+                (&d.problem, senders.get(&d.message_id), d.received)
+            {
+                if let Some(until) = progress.get_mut(*sender) {
+                    *until = (*until).min(received - Duration::nanoseconds(1));
+                }
+            }
+        }
+
+        progress
+    }
+
+    /// Stores `records` read from messages in `source`, replacing those already in the ledger
+    /// for the same message, and marks the messages of each contact as synced up to the time in
+    /// `progress`. Returns the number of records that weren't in the ledger yet
+    pub fn add(
+        &mut self,
+        source: &str,
+        records: &[Record],
+        progress: &HashMap<String, DateTime<Utc>>,
+    ) -> Result<usize, Error> {
+        let now = Ledger::format_time(&Utc::now());
+        let tx = self.conn.transaction()?;
+        let count = |tx: &Connection| -> rusqlite::Result<usize> {
+            tx.query_row("select count(*) from records", [], |row| row.get(0))
+        };
+        let before = count(&tx)?;
+
+        {
+            let mut stmt = tx.prepare(
+                "insert into records
+                    (message_source, message_id, matcher_id, nature, account, amount, currency,
+                    source, time, parsed_at)
+                values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                on conflict (message_source, message_id) do update set
+                    matcher_id = excluded.matcher_id,
+                    nature = excluded.nature,
+                    account = excluded.account,
+                    amount = excluded.amount,
+                    currency = excluded.currency,
+                    source = excluded.source,
+                    time = excluded.time,
+                    parsed_at = excluded.parsed_at",
+            )?;
+
+            for r in records {
+                stmt.execute(params![
+                    source,
+                    r.message_id,
+                    r.matcher_id,
+                    r.nature.to_string(),
+                    r.account,
+                    r.amount.amount().to_string(),
+                    r.amount.currency().iso_alpha_code,
+                    r.source,
+                    Ledger::format_time(&r.time),
+                    now,
+                ])?;
+            }
+        }
+
+        for (contact, until) in progress {
+            tx.execute(
+                "insert or replace into sync_progress (source, contact, synced_until, synced_at)
+                values (?, ?, ?, ?)",
+                params![source, contact, Ledger::format_time(until), now],
+            )?;
+        }
+        let added = count(&tx)? - before;
+        tx.commit()?;
+
+        Ok(added)
+    }
+
+    /// Loads the records between `start` and `end`
+    pub fn records(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<Record>, Error> {
+        let mut stmt = self.conn.prepare(
+            "select message_id, matcher_id, nature, account, amount, currency, source, time
+            from records
+            where time between ? and ?
+            order by time",
+        )?;
+
+        let rows = stmt.query_map(
+            params![Ledger::format_time(start), Ledger::format_time(end)],
+            |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, String>(7)?,
+                ))
+            },
+        )?;
+
+        rows.map(|row| {
+            let (message_id, matcher_id, nature, account, amount, currency, source, time) = row?;
+
+            Ok(Record {
+                matcher_id,
+                nature: Ledger::parse_nature(&nature)?,
+                message_id,
+                account,
+                amount: Ledger::parse_money(&amount, &currency)?,
+                source,
+                time: Ledger::parse_time(&time)?,
+                category: None,
+            })
+        })
+        .collect()
+    }
+
+    fn parse_nature(nature: &str) -> Result<Nature, Error> {
+        match nature {
+            "Credit" => Ok(Nature::Credit),
+            "Debit" => Ok(Nature::Debit),
+            "Reversal" => Ok(Nature::Reversal),
+            _ => Err(Error::InvalidRecord(format!("invalid nature: {}", nature))),
+        }
+    }
+
+    fn parse_money(amount: &str, currency: &str) -> Result<Money, Error> {
+        let currency = iso::find(currency)
+            .ok_or_else(|| Error::InvalidRecord(format!("unknown currency: {}", currency)))?;
+        let amount = Decimal::from_str(amount)
+            .map_err(|_| Error::InvalidRecord(format!("invalid amount: {}", amount)))?;
+
+        Ok(Money::from_decimal(amount, currency))
+    }
+
+    /// Times are stored as fixed width RFC 3339 strings so they sort chronologically
+    fn format_time(time: &DateTime<Utc>) -> String {
+        time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+    }
+
+    fn parse_time(time: &str) -> Result<DateTime<Utc>, Error> {
+        DateTime::parse_from_rfc3339(time)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|_| Error::InvalidRecord(format!("invalid time: {}", time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn message(id: u32, sender: &str, hour: u32) -> TextMessage {
+        TextMessage {
+            id,
+            sender: sender.to_string(),
+            text: String::new(),
+            time: Utc.ymd(2026, 10, 1).and_hms(hour, 0, 0),
+        }
+    }
+
+    fn record(message_id: u32, hour: u32) -> Record {
+        Record {
+            matcher_id: "m".to_string(),
+            nature: Nature::Debit,
+            message_id,
+            account: "1234".to_string(),
+            amount: Money::from_decimal(Decimal::from(-1500), iso::PKR),
+            source: "CAFE".to_string(),
+            time: Utc.ymd(2026, 10, 1).and_hms(hour, 0, 0),
+            category: None,
+        }
+    }
+
+    fn all_records(ledger: &Ledger) -> Vec<Record> {
+        ledger
+            .records(
+                &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+                &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn resyncing_replaces_records_of_the_same_message() {
+        let mut ledger = Ledger::open(":memory:").unwrap();
+        let progress = HashMap::new();
+        assert_eq!(
+            ledger
+                .add("Csv:a.csv", &[record(1, 5), record(2, 6)], &progress)
+                .unwrap(),
+            2
+        );
+
+        // the matcher's timezone was fixed, so the same messages are read with other times
+        assert_eq!(
+            ledger
+                .add("Csv:a.csv", &[record(1, 10), record(2, 11)], &progress)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            all_records(&ledger)
+                .iter()
+                .map(|r| (r.message_id, r.time))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Utc.ymd(2026, 10, 1).and_hms(10, 0, 0)),
+                (2, Utc.ymd(2026, 10, 1).and_hms(11, 0, 0)),
+            ]
+        );
+
+        // the same id in another source is another message
+        assert_eq!(ledger.add("ChatDb", &[record(1, 5)], &progress).unwrap(), 1);
+        assert_eq!(all_records(&ledger).len(), 3);
+    }
+
+    #[test]
+    fn migration_keeps_the_latest_copy_of_records_parsed_twice() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            migration(&conn).unwrap();
+        }
+        conn.execute_batch(
+            "pragma user_version = 3;
+            insert into sync_progress values ('Csv:a.csv', '8012', '', '');
+            insert into records
+                (message_id, matcher_id, nature, account, amount, currency, source, time,
+                parsed_at)
+            values
+                (1, 'm', 'Debit', 'a', '-5', 'USD', 'CAFE', '2026-10-01T05:00:00.000000000Z', ''),
+                (1, 'm', 'Debit', 'a', '-5', 'USD', 'CAFE', '2026-10-01T10:00:00.000000000Z', ''),
+                (2, 'm', 'Debit', 'a', '-7', 'USD', 'SHOP', '2026-10-01T11:00:00.000000000Z', '');",
+        )
+        .unwrap();
+        Ledger::migrate(&mut conn).unwrap();
+
+        let mut ledger = Ledger { conn };
+        assert_eq!(
+            all_records(&ledger)
+                .iter()
+                .map(|r| (r.message_id, r.time))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Utc.ymd(2026, 10, 1).and_hms(10, 0, 0)),
+                (2, Utc.ymd(2026, 10, 1).and_hms(11, 0, 0)),
+            ]
+        );
+        // records were assigned the only source synced so far
+        let progress = HashMap::new();
+        assert_eq!(
+            ledger.add("Csv:a.csv", &[record(1, 5)], &progress).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn migrates_ledgers_made_before_natures_were_stored() {
+        let path = std::env::temp_dir().join(format!("finny-ledger-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        Ledger::create_records(&conn).unwrap();
+        conn.execute_batch(
+            "insert into records
+                (message_id, matcher_id, account, amount, currency, source, time, parsed_at)
+            values
+                (1, 'm', 'a', '-12.50', 'USD', 'CAFE', '2026-10-01T10:00:00.000000000Z', ''),
+                (2, 'm', 'a', '100', 'USD', 'SALARY', '2026-10-02T10:00:00.000000000Z', '');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let ledger = Ledger::open(path.to_str().unwrap()).unwrap();
+        let records = ledger
+            .records(
+                &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+                &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            )
+            .unwrap();
+        assert_eq!(
+            records.iter().map(|r| r.nature).collect::<Vec<_>>(),
+            vec![Nature::Debit, Nature::Credit]
+        );
+
+        let version: usize = ledger
+            .conn
+            .query_row("pragma user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        drop(ledger);
+
+        // opening again runs nothing
+        Ledger::open(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn progress_is_the_latest_message_of_each_contact() {
+        // sources don't always return messages in order
+        let msgs = vec![
+            message(1, "8012", 5),
+            message(2, "8012", 3),
+            message(3, "9355", 1),
+        ];
+        let progress = Ledger::sync_progress(&msgs, &Diagnostics::new());
+
+        assert_eq!(progress["8012"], Utc.ymd(2026, 10, 1).and_hms(5, 0, 0));
+        assert_eq!(progress["9355"], Utc.ymd(2026, 10, 1).and_hms(1, 0, 0));
+    }
+
+    #[test]
+    fn progress_stops_before_failed_messages() {
+        let msgs = vec![
+            message(1, "8012", 1),
+            message(2, "8012", 2),
+            message(3, "8012", 3),
+        ];
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(
+            &msgs[1],
+            Problem::Failed {
+                matcher_id: "m".to_string(),
+                field: "amount".to_string(),
+                error: "invalid".to_string(),
+            },
+        );
+        diagnostics.push(&msgs[2], Problem::Unmatched);
+
+        let progress = Ledger::sync_progress(&msgs, &diagnostics);
+        assert_eq!(
+            progress["8012"],
+            Utc.ymd(2026, 10, 1).and_hms(2, 0, 0) - Duration::nanoseconds(1)
+        );
+    }
+
+    #[test]
+    fn sync_starts_are_kept_per_source_and_contact() {
+        let mut ledger = Ledger::open(":memory:").unwrap();
+        let default = Utc.ymd(2026, 1, 1).and_hms(0, 0, 0);
+        let until = Utc.ymd(2026, 10, 1).and_hms(5, 0, 0);
+        let progress = HashMap::from([("8012".to_string(), until)]);
+        ledger.add("SmsBackup:a.xml", &[], &progress).unwrap();
+
+        let contacts = vec!["8012".to_string(), "9355".to_string()];
+        let starts = ledger
+            .sync_starts("SmsBackup:a.xml", &contacts, &default)
+            .unwrap();
+        assert_eq!(starts["8012"], until + Duration::nanoseconds(1));
+        assert_eq!(starts["9355"], default);
+
+        let starts = ledger
+            .sync_starts("Csv:b.csv", &contacts, &default)
+            .unwrap();
+        assert_eq!(starts["8012"], default);
+    }
+}
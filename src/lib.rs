@@ -1,9 +1,21 @@
+pub mod budget;
+pub mod category;
+pub mod chat_db;
 pub mod config;
+pub mod diagnostics;
+pub mod examples;
+pub mod exchange;
+pub mod export;
+pub mod ledger;
+pub mod merchant;
 pub mod message;
 pub mod parser;
 pub mod process;
 pub mod record;
+pub mod sms_backup;
 pub mod tables;
+pub mod typedstream;
+pub mod validate;
 pub mod wrapper;
 
 pub use process::*;
@@ -1,13 +1,33 @@
+use std::process;
+
 use chrono::DateTime;
 use chrono::Utc;
 use clap::Parser;
+use finny::budget;
+use finny::category::categorize;
+use finny::config;
 use finny::config::Config;
-use finny::message::TextMessage;
+use finny::diagnostics::Diagnostics;
+use finny::examples::run_examples;
+use finny::exchange::Exchange;
+use finny::exchange::Rate;
+use finny::ledger::Ledger;
+use finny::message::SourceKind;
+use finny::process::filter_only;
 use finny::process::filter_out_sources;
+use finny::process::GroupBy;
+use finny::process::Interval;
+use finny::process::Only;
 use finny::record::Record;
+use finny::tables::BudgetTable;
+use finny::tables::DiagnosticsTable;
+use finny::tables::ExamplesTable;
+use finny::tables::PeriodsTable;
 use finny::tables::SubscriptionsTable;
+use finny::tables::TotalsMode;
 use finny::tables::TotalsTable;
 use finny::tables::TransactionsTable;
+use finny::wrapper::Currency;
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -15,6 +35,8 @@ lazy_static! {
         vec!["JS Credit Card Bill Pay From IB".to_string()];
 }
 
+const DEFAULT_LEDGER: &str = "./ledger.db";
+
 /// Calculate your expenses from messages sent by your bank
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about=None)]
@@ -52,6 +74,46 @@ struct Args {
     #[clap(long, value_parser, global = true, default_values_t=DEFAULT_EXCLUDE_SOURCES.iter())]
     exclude_sources: Vec<String>,
 
+    /// Only include money going out or coming in
+    #[clap(long, value_enum, global = true)]
+    only: Option<Only>,
+
+    /// Where to read messages from, overrides the source configured in the config file
+    #[clap(long, value_enum, global = true)]
+    source: Option<SourceKind>,
+
+    /// Path to a Messages app database to read instead of the one in the home directory
+    #[clap(long, value_parser, global = true)]
+    messages_db: Option<String>,
+
+    /// Path to the file to read messages from when using a file based source, `-` for stdin
+    #[clap(short, long, value_parser, global = true)]
+    input: Option<String>,
+
+    /// Path to the ledger database that synced transactions are stored in [default: ./ledger.db]
+    #[clap(long, value_parser, global = true)]
+    ledger: Option<String>,
+
+    /// Read transactions from the ledger instead of from messages
+    #[clap(long, value_parser, global = true, action)]
+    from_ledger: bool,
+
+    /// Currency to convert amounts to for reporting, overrides the one in the config file
+    #[clap(long, value_parser=str::parse::<Currency>, global = true)]
+    currency: Option<Currency>,
+
+    /// Exchange rates as FROM/TO=RATE, added to the ones in the config file
+    #[clap(long, value_parser=str::parse::<Rate>, global = true)]
+    rate: Vec<Rate>,
+
+    /// Path to a CSV or JSON file of daily exchange rates, overrides the one in the config file
+    #[clap(long, value_parser, global = true)]
+    rates_file: Option<String>,
+
+    /// How amounts in different currencies are totalled
+    #[clap(long, value_enum, global = true, default_value_t = TotalsMode::Converted)]
+    totals: TotalsMode,
+
     /// Path to the matchers config
     #[clap(
         short,
@@ -80,33 +142,246 @@ enum Command {
 
     /// Shows detected subscriptions from your data
     Subscriptions,
+
+    /// Shows aggregated totals for each category
+    Categories,
+
+    /// Shows spending against the configured budgets for each period in the window
+    Budget,
+
+    /// Shows debits, credits and net for each period in the window
+    Periods {
+        /// Length of each period
+        #[clap(long, value_enum, default_value_t = Interval::Month)]
+        by: Interval,
+
+        /// Show the net of each category or source in its own column
+        #[clap(long, value_enum)]
+        pivot: Option<GroupBy>,
+    },
+
+    /// Imports transactions from messages received since the last sync into the ledger
+    Sync {
+        /// Read all messages from --start again, for example after fixing a matcher, records
+        /// already in the ledger are replaced by the ones parsed again
+        #[clap(long, value_parser)]
+        resync: bool,
+    },
+
+    /// Lists messages that no matcher matched or that failed to parse, along with records whose
+    /// time looks wrong
+    Unmatched {
+        /// Also list messages that more than one matcher matched, with the matcher that was used
+        #[clap(long, value_parser)]
+        overlaps: bool,
+    },
+
+    /// Checks that every matcher reads its examples as expected, exits with an error otherwise
+    TestConfig,
+}
+
+/// Points at `finny unmatched` when some messages couldn't be parsed
+fn report_diagnostics(diagnostics: &Diagnostics) {
+    let (unreadable, unmatched, failed, times) = diagnostics.counts();
+    if unreadable + unmatched + failed + times == 0 {
+        return;
+    }
+
+    eprintln!(
+        "{} messages couldn't be read, {} matched no pattern, {} failed to parse and {} records have suspicious times, run `finny unmatched` for details",
+        unreadable, unmatched, failed, times
+    );
 }
 
 fn main() {
     let args = Args::parse();
-    let msgs = TextMessage::fetch(
-        &args.contacts.iter().map(|s| &s[..]).collect(),
-        &args.start,
-        &args.end,
-    )
-    .unwrap();
-
-    let config = Config::new(&args.config).expect("Error parsing configuration");
-    let mut records = Record::parse_messages(&config.matchers, &msgs);
+    let mut config = match Config::new(&args.config) {
+        Err(config::Error::Invalid(problems)) => {
+            eprintln!("Found {} problems in {}:", problems.len(), args.config);
+            for problem in problems {
+                eprintln!("  {}", problem);
+            }
+            process::exit(1);
+        }
+        config => config.expect("Error parsing configuration"),
+    };
+    if let Some(kind) = args.source {
+        config.source.kind = kind;
+    }
+    if let Some(path) = args.messages_db {
+        config.source.messages_db = Some(path);
+    }
+    if let Some(path) = args.input {
+        config.source.input = Some(path);
+    }
+    if let Some(path) = args.ledger {
+        config.ledger = Some(path);
+    }
+    if let Some(currency) = args.currency {
+        config.currency.reporting = currency;
+    }
+    config.currency.rates.extend(args.rate);
+    if let Some(path) = args.rates_file {
+        config.currency.history = Some(path);
+    }
+
+    let ledger_path = config.ledger.as_deref().unwrap_or(DEFAULT_LEDGER);
+
+    if let Command::Sync { resync } = args.subcommand {
+        let mut ledger = Ledger::open(ledger_path).expect("Error opening ledger");
+        let source = config.source.name();
+        let starts = if resync {
+            args.contacts
+                .iter()
+                .map(|c| (c.clone(), args.start))
+                .collect()
+        } else {
+            ledger
+                .sync_starts(&source, &args.contacts, &args.start)
+                .unwrap()
+        };
+
+        let mut diagnostics = Diagnostics::new();
+        let start = starts.values().min().copied().unwrap_or(args.start);
+        let msgs: Vec<_> = config
+            .source
+            .build()
+            .expect("Error opening message source")
+            .fetch(&args.contacts, &start, &args.end, &mut diagnostics)
+            .unwrap()
+            .into_iter()
+            .filter(|m| matches!(starts.get(&m.sender), Some(start) if &m.time >= start))
+            .collect();
+
+        let records = Record::parse_messages(
+            &config.matchers,
+            &config.merchants,
+            &msgs,
+            false,
+            &mut diagnostics,
+        );
+        report_diagnostics(&diagnostics);
+        let progress = Ledger::sync_progress(&msgs, &diagnostics);
+        let added = ledger.add(&source, &records, &progress).unwrap();
+        println!(
+            "Synced {} new transactions from {} messages",
+            added,
+            msgs.len()
+        );
+        return;
+    }
+
+    // budgets and periods show whole periods, so read from the start of the first one
+    let start = match args.subcommand {
+        Command::Budget => budget::window_start(&config.budgets, &args.start),
+        Command::Periods { by, .. } => DateTime::from_utc(
+            by.start_of(args.start.naive_utc().date()).and_hms(0, 0, 0),
+            Utc,
+        ),
+        _ => args.start,
+    };
+
+    if let Command::TestConfig = args.subcommand {
+        let results = run_examples(&config.matchers, &config.merchants);
+        let failed = results.iter().filter(|r| !r.passed()).count();
+        let overlapping = results.iter().filter(|r| !r.overlaps.is_empty()).count();
+        println!("{}", ExamplesTable::new(&results));
+        println!(
+            "{} examples passed, {} failed, {} also matched by other matchers",
+            results.len() - failed,
+            failed,
+            overlapping
+        );
+        if failed > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let (Command::Unmatched { .. }, true) = (&args.subcommand, args.from_ledger) {
+        eprintln!("unmatched messages can only be listed when reading from messages");
+        process::exit(1);
+    }
+
+    let msgs;
+    let mut diagnostics = Diagnostics::new();
+    let mut records = if args.from_ledger {
+        Ledger::open(ledger_path)
+            .expect("Error opening ledger")
+            .records(&start, &args.end)
+            .unwrap()
+    } else {
+        msgs = config
+            .source
+            .build()
+            .expect("Error opening message source")
+            .fetch(&args.contacts, &start, &args.end, &mut diagnostics)
+            .unwrap();
+        Record::parse_messages(
+            &config.matchers,
+            &config.merchants,
+            &msgs,
+            matches!(args.subcommand, Command::Unmatched { overlaps: true }),
+            &mut diagnostics,
+        )
+    };
+
+    if let Command::Unmatched { .. } = args.subcommand {
+        println!("{}", DiagnosticsTable::new(&diagnostics));
+        return;
+    }
+    report_diagnostics(&diagnostics);
     records = filter_out_sources(&records, &args.exclude_sources);
+    if let Some(only) = args.only {
+        records = filter_only(&records, only);
+    }
+    categorize(&config.categories, &mut records);
+
+    let exchange = Exchange::new(&config.currency).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    if args.totals.converts()
+        || matches!(args.subcommand, Command::Budget | Command::Periods { .. })
+    {
+        if let Err(err) = exchange.check(records.iter().map(|r| (&r.amount, &r.time))) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
 
     match args.subcommand {
         Command::Transactions { show_matcher } => {
-            let v = TransactionsTable::new(&records, show_matcher);
+            let v = TransactionsTable::new(&records, &exchange, args.totals, show_matcher);
             println!("{}", v);
         }
         Command::Totals => {
-            let v = TotalsTable::new(&records);
+            let v = TotalsTable::new(&records, &exchange, args.totals, GroupBy::Source);
             println!("{}", v);
         }
         Command::Subscriptions => {
-            let v = SubscriptionsTable::new(&records);
+            let v = SubscriptionsTable::new(&records, &exchange, args.totals);
+            println!("{}", v);
+        }
+        Command::Categories => {
+            let v = TotalsTable::new(&records, &exchange, args.totals, GroupBy::Category);
+            println!("{}", v);
+        }
+        Command::Budget => {
+            let statuses = budget::budget_statuses(
+                &config.budgets,
+                &records,
+                &exchange,
+                &args.start,
+                &args.end,
+            )
+            .unwrap();
+            println!("{}", BudgetTable::new(&statuses));
+        }
+        Command::Periods { by, pivot } => {
+            let v = PeriodsTable::new(&records, &exchange, by, &args.start, &args.end, pivot);
             println!("{}", v);
         }
+        Command::Sync { .. } | Command::Unmatched { .. } | Command::TestConfig => unreachable!(),
     }
 }
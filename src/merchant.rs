@@ -0,0 +1,195 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+lazy_static! {
+    /// A `*` followed by a reference code at the end of a name, e.g. `AMAZON.COM*AB12CD`. Codes
+    /// must contain a digit so names like `PAYPAL *NETFLIX` are kept
+    static ref REFERENCE_SUFFIX: Regex = Regex::new(r"\s*\*([A-Za-z0-9]{4,})$").unwrap();
+    /// A store number such as `#1234` or `0423`, shorter numbers are usually part of the name
+    static ref STORE_NUMBER: Regex = Regex::new(r"^(#\d+|\d{4,})$").unwrap();
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+const BUILTIN_CITIES: &[&str] = &[
+    "karachi",
+    "lahore",
+    "islamabad",
+    "rawalpindi",
+    "faisalabad",
+    "peshawar",
+    "multan",
+    "quetta",
+    "singapore",
+    "dubai",
+    "london",
+    "new york",
+    "san francisco",
+    "seattle",
+    "los gatos",
+    "dublin",
+    "luxembourg",
+];
+
+const COUNTRY_CODES: &[&str] = &[
+    "pk", "pak", "us", "usa", "sg", "sgp", "gb", "uk", "ae", "ie", "lu",
+];
+
+/// Maps merchant names matching `pattern` to `name`
+#[derive(Debug, Deserialize)]
+pub struct Alias {
+    #[serde(with = "serde_regex")]
+    pub pattern: Regex,
+    pub name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MerchantConfig {
+    /// Checked in order against the name as it appears in the message, before any cleanup
+    pub aliases: Vec<Alias>,
+    /// Strip reference codes, store numbers, city names and country codes from the end of names,
+    /// off by default
+    pub cleanup: bool,
+    /// City names to strip in addition to the built-in ones
+    pub cities: Vec<String>,
+}
+
+impl MerchantConfig {
+    /// The canonical name of a merchant as captured from a message
+    pub fn normalize(&self, name: &str) -> String {
+        if let Some(alias) = self.aliases.iter().find(|a| a.pattern.is_match(name)) {
+            return alias.name.clone();
+        }
+
+        let name = WHITESPACE.replace_all(name.trim(), " ").to_string();
+        if !self.cleanup {
+            return name;
+        }
+
+        let mut cleaned = name.clone();
+        if let Some(captures) = REFERENCE_SUFFIX.captures(&name) {
+            let start = captures.get(0).unwrap().start();
+            if start > 0 && captures[1].chars().any(|c| c.is_ascii_digit()) {
+                cleaned.truncate(start);
+            }
+        }
+
+        while let Some(stripped) = self.strip_suffix(&cleaned) {
+            cleaned = stripped;
+        }
+        cleaned
+    }
+
+    /// Removes a single trailing store number, city or country code, never leaving the name
+    /// empty
+    fn strip_suffix(&self, name: &str) -> Option<String> {
+        let (rest, last) = name.rsplit_once(' ')?;
+        if rest.trim().is_empty() {
+            return None;
+        }
+
+        if STORE_NUMBER.is_match(last) || COUNTRY_CODES.contains(&last.to_lowercase().as_str()) {
+            return Some(rest.trim_end().to_string());
+        }
+
+        BUILTIN_CITIES
+            .iter()
+            .copied()
+            .chain(self.cities.iter().map(String::as_str))
+            .filter_map(|city| strip_suffix_ignoring_case(name, city))
+            .map(str::trim_end)
+            .find(|rest| !rest.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// `name` without the word `suffix` at its end, ignoring case
+fn strip_suffix_ignoring_case<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let count = suffix.chars().count();
+    let start = name.char_indices().rev().nth(count.checked_sub(1)?)?.0;
+    let (rest, end) = name.split_at(start);
+
+    (rest.ends_with(' ') && end.to_lowercase() == suffix.to_lowercase()).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cleanup(cities: &[&str]) -> MerchantConfig {
+        MerchantConfig {
+            aliases: vec![],
+            cleanup: true,
+            cities: cities.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn aliases_are_checked_before_cleanup() {
+        let config = MerchantConfig {
+            aliases: vec![Alias {
+                pattern: Regex::new("(?i)^(amazon|amzn)").unwrap(),
+                name: "Amazon".to_string(),
+            }],
+            ..cleanup(&[])
+        };
+
+        for name in ["AMAZON.COM*AB12", "Amazon Mktp", "AMZN DIGITAL"] {
+            assert_eq!(config.normalize(name), "Amazon");
+        }
+    }
+
+    #[test]
+    fn strips_reference_codes() {
+        let config = cleanup(&[]);
+        assert_eq!(config.normalize("AMAZON.COM*AB12CD"), "AMAZON.COM");
+        assert_eq!(config.normalize("UBER *TRIP 1X2Y3Z"), "UBER *TRIP 1X2Y3Z");
+    }
+
+    #[test]
+    fn keeps_processor_prefixes() {
+        let config = cleanup(&[]);
+        assert_eq!(config.normalize("SQ *BLUE BOTTLE"), "SQ *BLUE BOTTLE");
+        assert_eq!(config.normalize("PAYPAL *NETFLIX"), "PAYPAL *NETFLIX");
+    }
+
+    #[test]
+    fn strips_store_numbers_cities_and_countries() {
+        let config = cleanup(&[]);
+        assert_eq!(config.normalize("WALGREENS #1234"), "WALGREENS");
+        assert_eq!(config.normalize("STARBUCKS 04231 KARACHI PK"), "STARBUCKS");
+        assert_eq!(
+            config.normalize("Netflix.com   Los Gatos US"),
+            "Netflix.com"
+        );
+    }
+
+    #[test]
+    fn keeps_numbers_that_are_part_of_the_name() {
+        let config = cleanup(&[]);
+        assert_eq!(config.normalize("Studio 54"), "Studio 54");
+        assert_eq!(config.normalize("Forever 21 Dubai"), "Forever 21");
+    }
+
+    #[test]
+    fn strips_cities_with_non_ascii_names() {
+        let config = cleanup(&["İstanbul", "Zürich"]);
+        assert_eq!(config.normalize("Kebab Evi İSTANBUL"), "Kebab Evi");
+        assert_eq!(config.normalize("Café Über ZÜRICH"), "Café Über");
+        assert_eq!(config.normalize("Zürich"), "Zürich");
+    }
+
+    #[test]
+    fn only_strips_whole_words() {
+        let config = cleanup(&[]);
+        assert_eq!(config.normalize("Spotify Dublin"), "Spotify");
+        assert_eq!(config.normalize("Cafe Mydublin"), "Cafe Mydublin");
+    }
+
+    #[test]
+    fn cleanup_is_off_by_default() {
+        let config = MerchantConfig::default();
+        assert_eq!(config.normalize("WALGREENS  #1234"), "WALGREENS #1234");
+    }
+}
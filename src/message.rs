@@ -1,33 +1,27 @@
 use std::error;
-use std::rc::Rc;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
 
 use chrono::DateTime;
-use chrono::TimeZone;
 use chrono::Utc;
-use rusqlite::params;
-use rusqlite::types::Value;
-use rusqlite::Connection;
+use clap::ValueEnum;
+use serde::Deserialize;
 use strum_macros::Display;
 
-const NSECS_SINCE_2001: i64 = 978307200000000000;
-const QUERY: &str = "
-select 
-	m.ROWID as id, 
-	m.text as text, 
-	m.date as century_epoch 
-from handle h 
-join message m 
-	on h.ROWID = m.handle_id  
-where 
-	h.id in rarray(?) and
-	m.date between ? and ?
-order by
-    m.date;
-";
+use crate::chat_db::ChatDb;
+use crate::diagnostics::Diagnostics;
+use crate::export::Export;
+use crate::export::ExportFormat;
+use crate::export::FieldMapping;
+use crate::sms_backup::SmsBackup;
 
 #[derive(Debug)]
 pub struct TextMessage {
     pub id: u32,
+    /// Contact the message was received from
+    pub sender: String,
     pub text: String,
     pub time: DateTime<Utc>,
 }
@@ -35,61 +29,142 @@ pub struct TextMessage {
 #[derive(Debug, Display)]
 pub enum Error {
     HomeDirNotFound,
+    InputNotConfigured,
+    FileReadFailure(io::Error),
     SqliteError(rusqlite::Error),
+    XmlError(quick_xml::Error),
+    CsvError(csv::Error),
+    JsonError(serde_json::Error),
 }
 
 impl error::Error for Error {}
 
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::FileReadFailure(error)
+    }
+}
+
 impl From<rusqlite::Error> for Error {
     fn from(error: rusqlite::Error) -> Error {
         Error::SqliteError(error)
     }
 }
 
-impl TextMessage {
-    pub fn fetch(
-        source: &Vec<&str>,
+impl From<quick_xml::Error> for Error {
+    fn from(error: quick_xml::Error) -> Error {
+        Error::XmlError(error)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Error {
+        Error::CsvError(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::JsonError(error)
+    }
+}
+
+/// A store of text messages that can be queried by sender and time
+pub trait MessageSource {
+    /// Fetches messages sent by any of `contacts` between `start` and `end`, ordered by time.
+    /// Messages that can't be read are skipped and recorded in `diagnostics`
+    fn fetch(
+        &self,
+        contacts: &[String],
         start: &DateTime<Utc>,
         end: &DateTime<Utc>,
-    ) -> Result<Vec<TextMessage>, Error> {
-        let mut home = match home::home_dir() {
-            Some(path) => path,
-            None => return Err(Error::HomeDirNotFound),
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<TextMessage>, Error>;
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, ValueEnum)]
+pub enum SourceKind {
+    /// The macOS Messages app database
+    #[default]
+    MessagesApp,
+    /// An XML backup made by the Android app "SMS Backup & Restore"
+    SmsBackup,
+    /// A CSV file with a header row, columns are picked using the configured mapping
+    Csv,
+    /// A file with a JSON object per line, fields are picked using the configured mapping
+    JsonLines,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SourceConfig {
+    #[serde(default)]
+    pub kind: SourceKind,
+    /// Path to a Messages app database, defaults to the one in the home directory
+    pub messages_db: Option<String>,
+    /// Path to the file to read messages from, for file based sources
+    pub input: Option<String>,
+    /// Which columns or fields hold each part of a message, for CSV and JSON Lines sources
+    #[serde(default)]
+    pub mapping: FieldMapping,
+}
+
+impl SourceConfig {
+    /// Identifies where messages are read from, so that sync progress is kept per source
+    pub fn name(&self) -> String {
+        let path = match self.kind {
+            SourceKind::MessagesApp => self.messages_db.as_deref(),
+            _ => self.input.as_deref(),
         };
-        home.push("Library/Messages/chat.db");
-
-        let conn = Connection::open(home)?;
-        rusqlite::vtab::array::load_module(&conn)?;
-
-        let mut stmt = conn.prepare(&QUERY)?;
-        let ids: Rc<Vec<_>> = Rc::new(
-            source
-                .iter()
-                .map(ToString::to_string)
-                .map(Value::from)
-                .collect(),
-        );
-
-        let msgs = stmt.query_map(
-            params![
-                ids,
-                start.timestamp_nanos() - NSECS_SINCE_2001,
-                (end.timestamp_nanos() - NSECS_SINCE_2001),
-            ],
-            |row| {
-                Ok(TextMessage {
-                    id: row.get(0)?,
-                    text: row.get(1)?,
-                    time: TextMessage::parse_time_from_century_epoch(row.get(2)?),
-                })
-            },
-        )?;
-
-        Ok(msgs.collect::<Result<Vec<_>, _>>()?)
+
+        match path {
+            Some(path) => format!("{:?}:{}", self.kind, path),
+            None => format!("{:?}", self.kind),
+        }
     }
 
-    fn parse_time_from_century_epoch(century_epoch: i64) -> DateTime<Utc> {
-        let epoch = century_epoch + NSECS_SINCE_2001;
-        Utc.timestamp(epoch / 1_000_000_000, (epoch % 1_000_000_000) as u32)
+    pub fn build(&self) -> Result<Box<dyn MessageSource>, Error> {
+        let input = || self.input.as_deref().ok_or(Error::InputNotConfigured);
+
+        match self.kind {
+            SourceKind::MessagesApp => Ok(Box::new(match &self.messages_db {
+                Some(path) => ChatDb::new(path),
+                None => ChatDb::from_home_dir()?,
+            })),
+            SourceKind::SmsBackup => Ok(Box::new(SmsBackup::new(input()?))),
+            SourceKind::Csv => Ok(Box::new(Export::new(
+                input()?,
+                ExportFormat::Csv,
+                self.mapping.clone(),
+            ))),
+            SourceKind::JsonLines => Ok(Box::new(Export::new(
+                input()?,
+                ExportFormat::JsonLines,
+                self.mapping.clone(),
+            ))),
+        }
+    }
+}
+
+/// Opens a file for buffered reading, `-` reads from stdin
+pub fn open_input(path: &str) -> Result<Box<dyn BufRead>, Error> {
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+/// An id derived from the content of a message, for sources that don't keep ids of their own.
+/// Uses 32 bit FNV-1a so ids stay the same across builds and platforms.
+pub fn stable_id(parts: &[&str]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            hash = (hash ^ 0xff).wrapping_mul(0x01000193);
+        }
+        for byte in part.bytes() {
+            hash = (hash ^ byte as u32).wrapping_mul(0x01000193);
+        }
     }
+    hash
 }
@@ -1,22 +1,37 @@
 use chrono::DateTime;
+use chrono::Datelike;
 use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
 use chrono::ParseError;
 use chrono::TimeZone;
 use chrono::Utc;
+use lazy_static::lazy_static;
 use regex::Captures;
 use regex::Regex;
 use rust_decimal::Decimal;
 use rusty_money::iso;
 use rusty_money::MoneyError;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::IntoDeserializer;
+use serde::de::MapAccess;
+use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::error;
 use std::fmt::Display;
 use std::num::ParseIntError;
+use std::str::FromStr;
 
+use crate::diagnostics::Diagnostics;
+use crate::diagnostics::Problem;
+use crate::merchant::MerchantConfig;
 use crate::message::TextMessage;
 use crate::record::Money;
 use crate::record::Record;
 use crate::wrapper::Currency;
+use crate::wrapper::Zone;
 
 #[derive(Debug)]
 pub struct Error(String);
@@ -50,53 +65,355 @@ impl Display for Error {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
 pub enum Nature {
-    #[allow(dead_code)]
     Credit,
     Debit,
+    /// Money returned for an earlier debit, it offsets spending rather than counting as income
+    #[serde(alias = "Refund")]
+    Reversal,
 }
 
 pub trait ValueParser<T: Clone> {
-    fn parse(&self, v: &str) -> Result<T, Error>;
+    /// Parses `v` captured from `msg`
+    fn parse(&self, v: &str, msg: &TextMessage) -> Result<T, Error>;
 }
 
 #[derive(Debug, Deserialize)]
 pub struct StringParser;
 
 impl ValueParser<String> for StringParser {
-    fn parse(&self, val: &str) -> Result<String, Error> {
+    fn parse(&self, val: &str, _msg: &TextMessage) -> Result<String, Error> {
         Ok(val.trim().to_string())
     }
 }
 
+/// Falls back to the default parser when none is configured
+impl<T: Clone, P: ValueParser<T> + Default> ValueParser<T> for Option<P> {
+    fn parse(&self, val: &str, msg: &TextMessage) -> Result<T, Error> {
+        match self {
+            Some(parser) => parser.parse(val, msg),
+            None => P::default().parse(val, msg),
+        }
+    }
+}
+
+/// Parses amounts written the way banks write them, e.g. `Rs.1,500/-`, `PKR 2,000.00`,
+/// `1.234,50` or `1,00,000`. Signs are dropped, whether money came in or went out is the nature
 #[derive(Debug, Deserialize)]
-pub struct CurrencyParser;
+#[serde(default)]
+pub struct AmountParser {
+    /// Separator between groups of digits, any grouping is accepted including lakh and crore
+    pub thousands_separator: String,
+    pub decimal_separator: String,
+}
+
+impl Default for AmountParser {
+    fn default() -> Self {
+        AmountParser {
+            thousands_separator: ",".to_string(),
+            decimal_separator: ".".to_string(),
+        }
+    }
+}
+
+impl AmountParser {
+    /// Words that may follow an amount, and what they multiply it by
+    const MULTIPLIERS: &'static [(&'static str, u32)] = &[
+        ("lakh", 100_000),
+        ("lakhs", 100_000),
+        ("lac", 100_000),
+        ("lacs", 100_000),
+        ("crore", 10_000_000),
+        ("crores", 10_000_000),
+    ];
+
+    /// Splits `val` into the number and whatever follows it, dropping signs, currency symbols and
+    /// codes before it
+    fn split_number<'v>(&self, val: &'v str) -> Option<(&'v str, &'v str)> {
+        let first = val.find(|c: char| c.is_ascii_digit())?;
+        let last = val.rfind(|c: char| c.is_ascii_digit())? + 1;
+
+        // keep a leading decimal separator for amounts like `.50`, unless it ends an
+        // abbreviation like `Rs.`
+        let prefix = &val[..first];
+        let start = match prefix.strip_suffix(self.decimal_separator.as_str()) {
+            Some(rest) if !rest.ends_with(char::is_alphabetic) => rest.len(),
+            _ => first,
+        };
+
+        Some((&val[start..last], &val[last..]))
+    }
+
+    /// Multiplier for a word like `lakh` following an amount
+    fn multiplier(suffix: &str) -> Option<Decimal> {
+        let word: String = suffix
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_alphabetic())
+            .collect::<String>()
+            .to_lowercase();
+
+        AmountParser::MULTIPLIERS
+            .iter()
+            .find(|(w, _)| *w == word)
+            .map(|(_, m)| Decimal::from(*m))
+    }
+}
+
+impl ValueParser<Decimal> for AmountParser {
+    fn parse(&self, val: &str, _msg: &TextMessage) -> Result<Decimal, Error> {
+        let invalid = || Error(format!("invalid amount: {}", val));
+        let (number, suffix) = self.split_number(val).ok_or_else(invalid)?;
+
+        let (whole, fraction) = match number.rsplit_once(self.decimal_separator.as_str()) {
+            Some((whole, fraction)) if !self.decimal_separator.is_empty() => {
+                (whole, Some(fraction))
+            }
+            _ => (number, None),
+        };
+        let mut digits = if self.thousands_separator.is_empty() {
+            whole.to_string()
+        } else {
+            whole.replace(self.thousands_separator.as_str(), "")
+        };
+        if let Some(fraction) = fraction {
+            digits.push('.');
+            digits.push_str(fraction);
+        }
+
+        let amount = Decimal::from_str(&digits).map_err(|_| invalid())?;
+        match AmountParser::multiplier(suffix) {
+            Some(multiplier) => Ok((amount * multiplier).normalize()),
+            None => Ok(amount),
+        }
+    }
+}
+
+/// Symbols and abbreviations banks write in place of ISO codes
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[
+    ("US$", "USD"),
+    ("S$", "SGD"),
+    ("A$", "AUD"),
+    ("C$", "CAD"),
+    ("HK$", "HKD"),
+    ("NZ$", "NZD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("₹", "INR"),
+    ("₩", "KRW"),
+    ("₱", "PHP"),
+    ("฿", "THB"),
+    ("Dhs", "AED"),
+    ("Dh", "AED"),
+];
+
+/// Symbols shared by several currencies, the first one is assumed unless told otherwise
+const AMBIGUOUS_CURRENCY_SYMBOLS: &[(&str, &[&str])] = &[
+    ("$", &["USD", "SGD", "AUD", "CAD", "HKD", "NZD"]),
+    ("Rs", &["PKR", "INR", "LKR", "NPR"]),
+    ("₨", &["PKR", "INR", "LKR", "NPR"]),
+    ("¥", &["JPY", "CNY"]),
+    ("kr", &["SEK", "NOK", "DKK"]),
+];
+
+/// What to do with a symbol like `$` that is shared by several currencies
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum Ambiguity {
+    /// Use the most common currency with that symbol
+    #[default]
+    Assume,
+    /// Fail unless an alias for the symbol is configured
+    Reject,
+}
+
+/// Recognizes ISO codes, common currency symbols and configured aliases, ignoring case and a
+/// trailing `.` as in `Rs.` or `PKR.`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CurrencyParser {
+    /// Symbols or names and the currency they stand for, checked before the built-in ones
+    pub aliases: HashMap<String, Currency>,
+    pub ambiguous: Ambiguity,
+}
+
+impl CurrencyParser {
+    fn normalize(val: &str) -> String {
+        val.trim().trim_end_matches('.').trim().to_lowercase()
+    }
+
+    fn builtin(&self, symbol: &str) -> Result<Option<&'static iso::Currency>, Error> {
+        let is_symbol = |s: &&str| CurrencyParser::normalize(s) == symbol;
+
+        if let Some((_, code)) = CURRENCY_SYMBOLS.iter().find(|(s, _)| is_symbol(s)) {
+            return Ok(iso::find(code));
+        }
+
+        match AMBIGUOUS_CURRENCY_SYMBOLS
+            .iter()
+            .find(|(s, _)| is_symbol(s))
+        {
+            Some((s, codes)) => match self.ambiguous {
+                Ambiguity::Assume => Ok(iso::find(codes[0])),
+                Ambiguity::Reject => Err(Error(format!(
+                    "currency symbol {} is ambiguous, it could be any of {}",
+                    s,
+                    codes.join(", ")
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+}
 
 impl ValueParser<Currency> for CurrencyParser {
-    fn parse(&self, val: &str) -> Result<Currency, Error> {
-        iso::find(val)
-            .map(|c| Currency(c))
-            .ok_or(Error("currency not recognized".to_string()))
+    fn parse(&self, val: &str, _msg: &TextMessage) -> Result<Currency, Error> {
+        let symbol = CurrencyParser::normalize(val);
+
+        if let Some((_, currency)) = self
+            .aliases
+            .iter()
+            .find(|(alias, _)| CurrencyParser::normalize(alias) == symbol)
+        {
+            return Ok(currency.clone());
+        }
+
+        if let Some(currency) = iso::find(&symbol.to_uppercase()) {
+            return Ok(Currency(currency));
+        }
+
+        match self.builtin(&symbol)? {
+            Some(currency) => Ok(Currency(currency)),
+            None => Err(Error(format!("currency not recognized: {}", val.trim()))),
+        }
+    }
+}
+
+/// Maps words captured from a message, ignoring case, to a nature
+#[derive(Debug, Deserialize)]
+pub struct NatureParser(HashMap<String, Nature>);
+
+impl ValueParser<Nature> for NatureParser {
+    fn parse(&self, val: &str, _msg: &TextMessage) -> Result<Nature, Error> {
+        let val = val.trim().to_lowercase();
+        self.0
+            .iter()
+            .find(|(word, _)| word.to_lowercase() == val)
+            .map(|(_, nature)| *nature)
+            .ok_or_else(|| Error(format!("nature not recognized: {}", val)))
     }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", content = "config")]
 pub enum DateTimeParser {
+    /// Date and time in the timezone of the machine finny runs on
     FormattedDateTime(String),
-    FormattedDateTimeWithAppend { format: String, suffix: String },
+    FormattedDateTimeWithAppend {
+        format: String,
+        suffix: String,
+    },
+    /// Date and time in `timezone`, trying each of `formats` in order. Formats without a year
+    /// use the year the message was received in, and formats without a time use the time it was
+    /// received at if it was received on the same day, midnight otherwise.
+    Formatted {
+        formats: Vec<String>,
+        #[serde(default)]
+        timezone: Zone,
+    },
+}
+
+impl DateTimeParser {
+    const YEAR_SPECIFIERS: &'static [&'static str] =
+        &["%Y", "%y", "%G", "%g", "%D", "%F", "%c", "%x", "%s"];
+    const TIME_SPECIFIERS: &'static [&'static str] =
+        &["%H", "%I", "%k", "%l", "%R", "%T", "%X", "%r", "%c", "%s"];
+
+    /// Normalizes ways of writing times that chrono doesn't accept, like `a.m.` or `10:30pm`
+    fn normalize(val: &str) -> String {
+        lazy_static! {
+            static ref MERIDIEM: Regex = Regex::new(r"(?i)(\d)\s*([ap])\.?\s*m\b\.?").unwrap();
+        }
+
+        let val = MERIDIEM.replace_all(val.trim(), |caps: &Captures| {
+            format!("{} {}M", &caps[1], caps[2].to_uppercase())
+        });
+        val.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Tries each of `formats` in turn, dates without a time get the time of day the message was
+    /// received at if `receipt_time_of_day` is set or if it was received on the same day
+    fn parse_formats(
+        formats: &[String],
+        val: &str,
+        timezone: &Zone,
+        msg: &TextMessage,
+        receipt_time_of_day: bool,
+    ) -> Result<DateTime<Utc>, Error> {
+        let mut error = Error("no formats configured".to_string());
+        for format in formats {
+            match DateTimeParser::parse_formatted(format, val, timezone, msg, receipt_time_of_day) {
+                Ok(time) => return Ok(time),
+                Err(err) => error = err,
+            }
+        }
+        Err(error)
+    }
+
+    fn parse_formatted(
+        format: &str,
+        val: &str,
+        timezone: &Zone,
+        msg: &TextMessage,
+        receipt_time_of_day: bool,
+    ) -> Result<DateTime<Utc>, Error> {
+        let has = |specifiers: &[&str]| specifiers.iter().any(|s| format.contains(s));
+        let received = timezone.local(&msg.time);
+        let val = DateTimeParser::normalize(val);
+
+        let parse = |year: i32| -> Result<NaiveDateTime, Error> {
+            let (format, val) = if has(DateTimeParser::YEAR_SPECIFIERS) {
+                (format.to_string(), val.clone())
+            } else {
+                (format!("{} %Y", format), format!("{} {}", val, year))
+            };
+
+            if has(DateTimeParser::TIME_SPECIFIERS) {
+                return Ok(NaiveDateTime::parse_from_str(&val, &format)?);
+            }
+            let date = NaiveDate::parse_from_str(&val, &format)?;
+            Ok(if receipt_time_of_day || date == received.date() {
+                date.and_time(received.time())
+            } else {
+                date.and_hms(0, 0, 0)
+            })
+        };
+
+        // a date without a year that lies ahead of the message was from the year before, like a
+        // `31 Dec` charge reported on the 1st of January
+        let mut time = parse(received.year())?;
+        if !has(DateTimeParser::YEAR_SPECIFIERS) && time.date() > received.date().succ() {
+            time = parse(received.year() - 1)?;
+        }
+
+        timezone
+            .to_utc(&time)
+            .ok_or_else(|| Error(format!("{} doesn't exist in timezone {:?}", time, timezone)))
+    }
 }
 
 impl ValueParser<DateTime<Utc>> for DateTimeParser {
-    fn parse(&self, val: &str) -> Result<DateTime<Utc>, Error> {
+    fn parse(&self, val: &str, msg: &TextMessage) -> Result<DateTime<Utc>, Error> {
         match self {
             DateTimeParser::FormattedDateTime(format) => {
-                Ok(Local.datetime_from_str(val, &format)?.with_timezone(&Utc))
+                Ok(Local.datetime_from_str(val, format)?.with_timezone(&Utc))
             }
             DateTimeParser::FormattedDateTimeWithAppend { format, suffix } => Ok(Local
-                .datetime_from_str(&format!("{}{}", val, suffix), &format)?
+                .datetime_from_str(&format!("{}{}", val, suffix), format)?
                 .with_timezone(&Utc)),
+            DateTimeParser::Formatted { formats, timezone } => {
+                DateTimeParser::parse_formats(formats, val, timezone, msg, false)
+            }
         }
     }
 }
@@ -105,14 +422,171 @@ impl ValueParser<DateTime<Utc>> for DateTimeParser {
 #[serde(tag = "type", content = "config")]
 pub enum Value<T: Clone, R: ValueParser<T>> {
     Fixed(T),
-    FromMatch { group: String, parser: R },
+    /// Read from `group`, or the first of `fallback` that took part in the match. `default` is
+    /// used if none did, otherwise the message fails to parse.
+    FromMatch {
+        group: String,
+        parser: R,
+        #[serde(default)]
+        fallback: Vec<String>,
+        default: Option<T>,
+    },
 }
 
 impl<T: Clone, R: ValueParser<T>> Value<T, R> {
-    fn extract(&self, captures: &Captures) -> Result<T, Error> {
+    fn extract(&self, captures: &Captures, msg: &TextMessage) -> Result<T, Error> {
         match self {
             Value::Fixed(value) => Ok(value.clone()),
-            Value::FromMatch { group, parser } => Ok(parser.parse(&captures[&group as &str])?),
+            Value::FromMatch {
+                group,
+                parser,
+                fallback,
+                default,
+            } => captured_or(captures, group, fallback, default, |text| {
+                parser.parse(text, msg)
+            }),
+        }
+    }
+}
+
+/// Text of `group`, or of the first of `fallback` that took part in the match
+fn captured<'t>(
+    captures: &Captures<'t>,
+    group: &str,
+    fallback: &[String],
+) -> Result<&'t str, Error> {
+    std::iter::once(group)
+        .chain(fallback.iter().map(String::as_str))
+        .find_map(|group| captures.name(group))
+        .map(|m| m.as_str())
+        .ok_or_else(|| {
+            if fallback.is_empty() {
+                Error(format!("group {} didn't match", group))
+            } else {
+                Error(format!(
+                    "none of the groups {}, {} matched",
+                    group,
+                    fallback.join(", ")
+                ))
+            }
+        })
+}
+
+/// `parse` applied to the text of `group` or of the first of `fallback` that took part in the
+/// match, `default` if none did
+fn captured_or<T: Clone>(
+    captures: &Captures,
+    group: &str,
+    fallback: &[String],
+    default: &Option<T>,
+    parse: impl FnOnce(&str) -> Result<T, Error>,
+) -> Result<T, Error> {
+    match (captured(captures, group, fallback), default) {
+        (Ok(text), _) => parse(text),
+        (Err(_), Some(default)) => Ok(default.clone()),
+        (Err(err), None) => Err(err),
+    }
+}
+
+/// Where the time of a record comes from
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "config")]
+pub enum TimeValue {
+    Fixed(DateTime<Utc>),
+    FromMatch {
+        group: String,
+        parser: DateTimeParser,
+        /// Groups tried in order if `group` didn't take part in the match
+        #[serde(default)]
+        fallback: Vec<String>,
+        /// Used if none of the groups took part in the match
+        default: Option<DateTime<Utc>>,
+    },
+    /// When the message was received, for banks that don't include a time
+    MessageTime,
+    /// A date read from the message using `formats`, at the time of day the message was received
+    DateFromMatch {
+        group: String,
+        formats: Vec<String>,
+        #[serde(default)]
+        timezone: Zone,
+        #[serde(default)]
+        fallback: Vec<String>,
+        default: Option<DateTime<Utc>>,
+    },
+}
+
+impl TimeValue {
+    fn extract(&self, captures: &Captures, msg: &TextMessage) -> Result<DateTime<Utc>, Error> {
+        match self {
+            TimeValue::Fixed(time) => Ok(*time),
+            TimeValue::FromMatch {
+                group,
+                parser,
+                fallback,
+                default,
+            } => captured_or(captures, group, fallback, default, |text| {
+                parser.parse(text, msg)
+            }),
+            TimeValue::MessageTime => Ok(msg.time),
+            TimeValue::DateFromMatch {
+                group,
+                formats,
+                timezone,
+                fallback,
+                default,
+            } => captured_or(captures, group, fallback, default, |text| {
+                DateTimeParser::parse_formats(formats, text, timezone, msg, true)
+            }),
+        }
+    }
+}
+
+/// Either a nature fixed for the matcher, or a value read from the message
+#[derive(Debug)]
+pub enum NatureConfig {
+    Fixed(Nature),
+    Value(Value<Nature, NatureParser>),
+}
+
+struct NatureConfigVisitor;
+
+impl<'de> Visitor<'de> for NatureConfigVisitor {
+    type Value = NatureConfig;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a nature, or a map saying how to read it from the message")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Nature::deserialize(v.into_deserializer()).map(NatureConfig::Fixed)
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        Value::deserialize(MapAccessDeserializer::new(map)).map(NatureConfig::Value)
+    }
+}
+
+impl<'de> Deserialize<'de> for NatureConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NatureConfigVisitor)
+    }
+}
+
+impl NatureConfig {
+    fn extract(&self, captures: &Captures, msg: &TextMessage) -> Result<Nature, Error> {
+        match self {
+            NatureConfig::Fixed(nature) => Ok(*nature),
+            NatureConfig::Value(value) => value.extract(captures, msg),
         }
     }
 }
@@ -120,10 +594,10 @@ impl<T: Clone, R: ValueParser<T>> Value<T, R> {
 #[derive(Debug, Deserialize)]
 pub struct ValuesConfig {
     pub account: Value<String, StringParser>,
-    pub amount: Value<String, StringParser>,
-    pub currency: Value<Currency, CurrencyParser>,
+    pub amount: Value<Decimal, Option<AmountParser>>,
+    pub currency: Value<Currency, Option<CurrencyParser>>,
     pub source: Value<String, StringParser>,
-    pub time: Value<DateTime<Utc>, DateTimeParser>,
+    pub time: TimeValue,
 }
 
 #[derive(Debug, Deserialize)]
@@ -131,63 +605,199 @@ pub struct Matcher {
     pub id: String,
     #[serde(with = "serde_regex")]
     pub pattern: Regex,
-    pub nature: Nature,
+    pub nature: NatureConfig,
     pub values: ValuesConfig,
+    /// Matchers with a higher priority are tried first, those with the same priority in the
+    /// order they are configured
+    #[serde(default)]
+    pub priority: i32,
+    /// Contacts whose messages this matcher applies to, all contacts if empty
+    #[serde(default)]
+    pub senders: Vec<String>,
+    /// Records whose time is more than this many hours away from when the message was received
+    /// are flagged, zero turns the check off
+    #[serde(default = "Matcher::default_time_window")]
+    pub time_window_hours: u32,
+    /// Sample messages and what should be read from them, see `finny test-config`
+    #[serde(default)]
+    pub examples: Vec<Example>,
+}
+
+/// A sample message and the values expected from it, values that aren't given aren't checked
+#[derive(Debug, Deserialize)]
+pub struct Example {
+    pub text: String,
+    /// Contact the message is from, defaults to the first of the matcher's senders
+    pub sender: Option<String>,
+    /// When the message was received, defaults to the expected time and is required without it
+    pub received: Option<DateTime<Utc>>,
+    pub nature: Option<Nature>,
+    pub account: Option<String>,
+    /// The amount as written in the message, without a sign
+    pub amount: Option<Decimal>,
+    pub currency: Option<Currency>,
+    /// The source after merchant aliases and cleanup
+    pub source: Option<String>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl Matcher {
+    fn default_time_window() -> u32 {
+        7 * 24
+    }
+
+    /// Whether `msg` is from one of the senders this matcher applies to
+    fn applies_to(&self, msg: &TextMessage) -> bool {
+        self.senders.is_empty() || self.senders.contains(&msg.sender)
+    }
+
+    /// Whether this matcher applies to `msg` and its pattern matches it
+    fn matches(&self, msg: &TextMessage) -> bool {
+        self.applies_to(msg) && self.pattern.is_match(&msg.text)
+    }
+
+    /// What the pattern captured from `msg`, if this matcher applies to it
+    fn captures<'t>(&self, msg: &'t TextMessage) -> Option<Captures<'t>> {
+        if self.applies_to(msg) {
+            self.pattern.captures(&msg.text)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the time read from `msg` is close enough to when it was received to be believable
+    fn is_plausible(&self, time: &DateTime<Utc>, msg: &TextMessage) -> bool {
+        self.time_window_hours == 0
+            || (*time - msg.time).num_seconds().abs() <= self.time_window_hours as i64 * 3600
+    }
 }
 
 pub struct RecordParser<'a> {
-    matchers: &'a Vec<Matcher>,
+    /// Ordered by priority
+    matchers: Vec<&'a Matcher>,
+    merchants: &'a MerchantConfig,
+    /// Whether to check the matchers after the one used, to report overlaps
+    overlaps: bool,
 }
 
 impl<'a> RecordParser<'a> {
-    pub fn new(matchers: &'a Vec<Matcher>) -> RecordParser<'a> {
+    pub fn new(
+        matchers: &'a [Matcher],
+        merchants: &'a MerchantConfig,
+        overlaps: bool,
+    ) -> RecordParser<'a> {
+        let mut matchers: Vec<_> = matchers.iter().collect();
+        matchers.sort_by_key(|m| std::cmp::Reverse(m.priority));
+
         RecordParser {
-            matchers: &matchers,
+            matchers,
+            merchants,
+            overlaps,
         }
     }
 
-    pub fn parse(&self, msg: &TextMessage) -> Option<Record<'a>> {
-        let matcher = self
+    /// Parses `msg` with the highest priority matcher that matches it, recording why in
+    /// `diagnostics` if that isn't possible, and which other matchers matched it too if asked to
+    pub fn parse(&self, msg: &TextMessage, diagnostics: &mut Diagnostics) -> Option<Record> {
+        let found = self
             .matchers
             .iter()
-            .find(|m| m.pattern.is_match(&msg.text))?;
-
-        let captures = matcher
-            .pattern
-            .captures(&msg.text)
-            .expect("expected all captures to match");
-
-        match RecordParser::parse_record(&matcher, &captures, msg) {
-            Ok(record) => Some(record),
-            Err(err) => {
-                println!(
-                    "error while parsing record. message: {}, matcher-id: {}, pattern: {}, : {}",
-                    msg.text, matcher.id, matcher.pattern, err
+            .enumerate()
+            .find_map(|(i, m)| Some((i, *m, m.captures(msg)?)));
+        let (i, matcher, captures) = match found {
+            Some(found) => found,
+            None => {
+                diagnostics.push(msg, Problem::Unmatched);
+                return None;
+            }
+        };
+
+        if self.overlaps {
+            let others: Vec<_> = self.matchers[i + 1..]
+                .iter()
+                .filter(|m| m.matches(msg))
+                .map(|m| m.id.clone())
+                .collect();
+            if !others.is_empty() {
+                diagnostics.push(
+                    msg,
+                    Problem::Overlap {
+                        matcher_id: matcher.id.clone(),
+                        others,
+                    },
+                );
+            }
+        }
+
+        match self.parse_record(matcher, &captures, msg) {
+            Ok(record) => {
+                if !matcher.is_plausible(&record.time, msg) {
+                    diagnostics.push(
+                        msg,
+                        Problem::ImplausibleTime {
+                            matcher_id: matcher.id.clone(),
+                            time: record.time,
+                        },
+                    );
+                }
+                Some(record)
+            }
+            Err((field, err)) => {
+                diagnostics.push(
+                    msg,
+                    Problem::Failed {
+                        matcher_id: matcher.id.clone(),
+                        field: field.to_string(),
+                        error: err.to_string(),
+                    },
                 );
                 None
             }
         }
     }
 
+    /// Fails with the name of the value that couldn't be read
     fn parse_record(
-        matcher: &'a Matcher,
+        &self,
+        matcher: &Matcher,
         captures: &Captures,
         msg: &TextMessage,
-    ) -> Result<Record<'a>, Error> {
+    ) -> Result<Record, (&'static str, Error)> {
+        let field = |name: &'static str| move |err: Error| (name, err);
         let values = &matcher.values;
+        let nature = matcher
+            .nature
+            .extract(captures, msg)
+            .map_err(field("nature"))?;
+        let amount = values
+            .amount
+            .extract(captures, msg)
+            .map_err(field("amount"))?;
+        let currency = values
+            .currency
+            .extract(captures, msg)
+            .map_err(field("currency"))?;
+
         Ok(Record {
+            matcher_id: matcher.id.clone(),
+            nature,
             message_id: msg.id,
-            account: values.account.extract(captures)?,
+            account: values
+                .account
+                .extract(captures, msg)
+                .map_err(field("account"))?,
             amount: RecordParser::canonical_amount(
-                &Money::from_str(
-                    &values.amount.extract(captures)?,
-                    values.currency.extract(captures)?.0,
-                )?,
-                &matcher.nature,
+                &Money::from_decimal(amount, currency.0),
+                &nature,
+            ),
+            source: self.merchants.normalize(
+                &values
+                    .source
+                    .extract(captures, msg)
+                    .map_err(field("source"))?,
             ),
-            source: values.source.extract(captures)?,
-            time: values.time.extract(captures)?,
-            matcher,
+            time: values.time.extract(captures, msg).map_err(field("time"))?,
+            category: None,
         })
     }
 
@@ -195,10 +805,296 @@ impl<'a> RecordParser<'a> {
         Money::from_decimal(
             money.amount()
                 * match nature {
-                    Nature::Credit => Decimal::ONE,
+                    Nature::Credit | Nature::Reversal => Decimal::ONE,
                     Nature::Debit => Decimal::NEGATIVE_ONE,
                 },
             money.currency(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> TextMessage {
+        TextMessage {
+            id: 1,
+            sender: "8012".to_string(),
+            text: String::new(),
+            time: Utc.ymd(2022, 9, 24).and_hms(10, 0, 0),
+        }
+    }
+
+    fn matcher(id: &str, pattern: &str, priority: i32) -> Matcher {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "pattern": pattern,
+            "priority": priority,
+            "nature": "Debit",
+            "values": {
+                "account": {"type": "Fixed", "config": "1234"},
+                "amount": {"type": "FromMatch", "config": {"group": "amount", "parser": null}},
+                "currency": {"type": "Fixed", "config": "PKR"},
+                "source": {"type": "Fixed", "config": "Shop"},
+                "time": {"type": "MessageTime"},
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn uses_the_matcher_with_the_highest_priority() {
+        let matchers = [
+            matcher("broad", r"(?P<amount>[\d.]+)", 0),
+            matcher("paid", r"Paid (?P<amount>[\d.]+)", 1),
+        ];
+        let merchants = MerchantConfig::default();
+        let msg = TextMessage {
+            text: "Paid 12.50".to_string(),
+            ..message()
+        };
+
+        let mut diagnostics = Diagnostics::new();
+        let record = RecordParser::new(&matchers, &merchants, false)
+            .parse(&msg, &mut diagnostics)
+            .unwrap();
+        assert_eq!(record.matcher_id, "paid");
+        assert!(diagnostics.is_empty());
+
+        RecordParser::new(&matchers, &merchants, true).parse(&msg, &mut diagnostics);
+        let problems: Vec<_> = diagnostics.iter().map(|d| &d.problem).collect();
+        assert!(matches!(
+            problems.as_slice(),
+            [Problem::Overlap { matcher_id, others }] if matcher_id == "paid" && others == &["broad"]
+        ));
+    }
+
+    #[test]
+    fn uses_the_default_time_when_no_group_matched() {
+        let pattern = Regex::new(r"charged(?: on (?P<date>\S+))?").unwrap();
+        let time: TimeValue = serde_json::from_str(
+            r#"{"type": "DateFromMatch", "config": {
+                "group": "date", "formats": ["%d/%m/%Y"], "timezone": "UTC",
+                "default": "2022-01-01T00:00:00Z"
+            }}"#,
+        )
+        .unwrap();
+
+        let captures = pattern.captures("charged on 01/09/2022").unwrap();
+        assert_eq!(
+            time.extract(&captures, &message()).unwrap(),
+            Utc.ymd(2022, 9, 1).and_hms(10, 0, 0)
+        );
+        let captures = pattern.captures("charged").unwrap();
+        assert_eq!(
+            time.extract(&captures, &message()).unwrap(),
+            Utc.ymd(2022, 1, 1).and_hms(0, 0, 0)
+        );
+    }
+
+    fn amount(parser: &AmountParser, val: &str) -> Result<Decimal, Error> {
+        parser.parse(val, &message())
+    }
+
+    fn european() -> AmountParser {
+        AmountParser {
+            thousands_separator: ".".to_string(),
+            decimal_separator: ",".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_amounts_with_separators() {
+        let parser = AmountParser::default();
+        assert_eq!(
+            amount(&parser, "1,234.56").unwrap(),
+            Decimal::new(123456, 2)
+        );
+        assert_eq!(amount(&parser, "1,234").unwrap(), Decimal::from(1234));
+        assert_eq!(amount(&parser, "1,00,000").unwrap(), Decimal::from(100_000));
+        assert_eq!(
+            amount(&european(), "1.234,56").unwrap(),
+            Decimal::new(123456, 2)
+        );
+        assert_eq!(amount(&european(), "1.234").unwrap(), Decimal::from(1234));
+    }
+
+    #[test]
+    fn parses_amounts_with_symbols_and_signs() {
+        let parser = AmountParser::default();
+        assert_eq!(amount(&parser, "Rs.1,500/-").unwrap(), Decimal::from(1500));
+        assert_eq!(
+            amount(&parser, "PKR 2,000.00").unwrap(),
+            Decimal::new(200000, 2)
+        );
+        assert_eq!(amount(&parser, "$.50").unwrap(), Decimal::new(50, 2));
+        assert_eq!(amount(&parser, "-12.5").unwrap(), Decimal::new(125, 1));
+    }
+
+    #[test]
+    fn parses_lakh_and_crore() {
+        let parser = AmountParser::default();
+        assert_eq!(
+            amount(&parser, "12 lakh").unwrap(),
+            Decimal::from(1_200_000)
+        );
+        assert_eq!(
+            amount(&parser, "1.5 crore").unwrap(),
+            Decimal::from(15_000_000)
+        );
+        assert_eq!(
+            amount(&parser, "2.25 Lacs").unwrap(),
+            Decimal::from(225_000)
+        );
+    }
+
+    #[test]
+    fn rejects_text_without_amount() {
+        assert!(amount(&AmountParser::default(), "Rs.").is_err());
+        assert!(amount(&AmountParser::default(), "1.2.3").is_err());
+    }
+
+    fn currency(parser: &CurrencyParser, val: &str) -> Result<&'static str, Error> {
+        parser.parse(val, &message()).map(|c| c.0.iso_alpha_code)
+    }
+
+    #[test]
+    fn recognizes_codes_and_symbols() {
+        let parser = CurrencyParser::default();
+        assert_eq!(currency(&parser, "pkr.").unwrap(), "PKR");
+        assert_eq!(currency(&parser, " € ").unwrap(), "EUR");
+        assert_eq!(currency(&parser, "US$").unwrap(), "USD");
+        assert!(currency(&parser, "XYZ").is_err());
+    }
+
+    #[test]
+    fn assumes_the_most_common_currency_for_ambiguous_symbols() {
+        let parser = CurrencyParser::default();
+        assert_eq!(currency(&parser, "$").unwrap(), "USD");
+        assert_eq!(currency(&parser, "Rs.").unwrap(), "PKR");
+    }
+
+    #[test]
+    fn rejects_ambiguous_symbols_without_an_alias() {
+        let mut parser = CurrencyParser {
+            aliases: HashMap::new(),
+            ambiguous: Ambiguity::Reject,
+        };
+        assert!(currency(&parser, "$").is_err());
+        assert_eq!(currency(&parser, "US$").unwrap(), "USD");
+
+        parser.aliases.insert("$".to_string(), Currency(iso::SGD));
+        assert_eq!(currency(&parser, "$").unwrap(), "SGD");
+    }
+
+    /// Reads `val` with `format` in `timezone`, for a message received at `received`
+    fn time(
+        format: &str,
+        val: &str,
+        timezone: &str,
+        received: DateTime<Utc>,
+    ) -> Result<DateTime<Utc>, Error> {
+        let msg = TextMessage {
+            time: received,
+            ..message()
+        };
+        DateTimeParser::parse_formatted(format, val, &timezone.parse().unwrap(), &msg, false)
+    }
+
+    #[test]
+    fn normalizes_meridiems() {
+        assert_eq!(DateTimeParser::normalize("10:30pm"), "10:30 PM");
+        assert_eq!(DateTimeParser::normalize(" 9:05 a.m. "), "9:05 AM");
+        assert_eq!(DateTimeParser::normalize("24 Sep  10 P.M."), "24 Sep 10 PM");
+    }
+
+    #[test]
+    fn reads_times_in_named_and_fixed_timezones() {
+        let format = "%d/%m/%Y %H:%M";
+        let received = Utc.ymd(2022, 9, 24).and_hms(12, 0, 0);
+        assert_eq!(
+            time(format, "24/09/2022 15:30", "Asia/Karachi", received).unwrap(),
+            Utc.ymd(2022, 9, 24).and_hms(10, 30, 0)
+        );
+        assert_eq!(
+            time(format, "24/09/2022 15:30", "+05:30", received).unwrap(),
+            Utc.ymd(2022, 9, 24).and_hms(10, 0, 0)
+        );
+    }
+
+    #[test]
+    fn reads_meridiems_and_month_names() {
+        let received = Utc.ymd(2022, 9, 24).and_hms(12, 0, 0);
+        assert_eq!(
+            time("%d %b %Y %I:%M %p", "5 Sep 2022 10:30pm", "UTC", received).unwrap(),
+            Utc.ymd(2022, 9, 5).and_hms(22, 30, 0)
+        );
+        assert_eq!(
+            time(
+                "%B %d, %Y %I:%M %p",
+                "September 5, 2022 9:15 a.m.",
+                "UTC",
+                received
+            )
+            .unwrap(),
+            Utc.ymd(2022, 9, 5).and_hms(9, 15, 0)
+        );
+    }
+
+    #[test]
+    fn takes_the_year_from_the_receipt_time() {
+        let received = Utc.ymd(2021, 9, 24).and_hms(12, 0, 0);
+        assert_eq!(
+            time("%d %b %H:%M", "20 Sep 09:15", "UTC", received).unwrap(),
+            Utc.ymd(2021, 9, 20).and_hms(9, 15, 0)
+        );
+        // a day ahead is allowed for clocks that disagree
+        assert_eq!(
+            time("%d %b %H:%M", "25 Sep 01:00", "UTC", received).unwrap(),
+            Utc.ymd(2021, 9, 25).and_hms(1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn takes_the_year_before_for_dates_ahead_of_the_receipt_time() {
+        let received = Utc.ymd(2023, 1, 1).and_hms(0, 5, 0);
+        assert_eq!(
+            time("%d %b %H:%M", "31 Dec 23:50", "UTC", received).unwrap(),
+            Utc.ymd(2022, 12, 31).and_hms(23, 50, 0)
+        );
+        // the receipt time is read in the timezone, where it is already the 1st of January
+        let received = Utc.ymd(2022, 12, 31).and_hms(20, 0, 0);
+        assert_eq!(
+            time("%d %b", "31 Dec", "Asia/Karachi", received).unwrap(),
+            Utc.ymd(2022, 12, 30).and_hms(19, 0, 0)
+        );
+    }
+
+    #[test]
+    fn uses_the_receipt_time_for_dates_on_the_same_day() {
+        let received = Utc.ymd(2022, 9, 24).and_hms(10, 0, 0);
+        assert_eq!(
+            time("%d/%m/%Y", "24/09/2022", "Asia/Karachi", received).unwrap(),
+            received
+        );
+        assert_eq!(
+            time("%d/%m/%Y", "23/09/2022", "Asia/Karachi", received).unwrap(),
+            Utc.ymd(2022, 9, 22).and_hms(19, 0, 0)
+        );
+    }
+
+    #[test]
+    fn rejects_times_skipped_by_daylight_saving() {
+        let format = "%d/%m/%Y %H:%M";
+        let received = Utc.ymd(2022, 11, 10).and_hms(12, 0, 0);
+        let err = time(format, "13/03/2022 02:30", "America/New_York", received).unwrap_err();
+        assert!(err.0.contains("doesn't exist in timezone"), "{}", err);
+
+        // times repeated when clocks go back are read as the earlier one
+        assert_eq!(
+            time(format, "06/11/2022 01:30", "America/New_York", received).unwrap(),
+            Utc.ymd(2022, 11, 6).and_hms(5, 30, 0)
+        );
+    }
+}
@@ -2,101 +2,144 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::vec;
 
+use chrono::DateTime;
 use chrono::Datelike;
-use lazy_static::lazy_static;
-use rust_decimal_macros::dec;
-use rusty_money::iso;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Utc;
+use clap::ValueEnum;
 use rusty_money::iso::Currency;
-use rusty_money::Exchange;
-use rusty_money::ExchangeRate;
 
+use crate::category::UNCATEGORIZED;
+use crate::exchange;
+use crate::exchange::Exchange;
+use crate::parser::Nature;
 use crate::record::Money;
 use crate::record::Record;
 
-pub fn filter_out_sources<'a>(records: &Vec<Record<'a>>, sources: &Vec<String>) -> Vec<Record<'a>> {
+pub fn filter_out_sources(records: &[Record], sources: &[String]) -> Vec<Record> {
     records
-        .clone()
-        .into_iter()
+        .iter()
         .filter(|r| !sources.contains(&r.source))
+        .cloned()
         .collect()
 }
 
-pub fn filter_in_sources<'a>(records: &Vec<Record<'a>>, sources: &Vec<String>) -> Vec<Record<'a>> {
+pub fn filter_in_sources(records: &[Record], sources: &[String]) -> Vec<Record> {
     records
-        .clone()
-        .into_iter()
+        .iter()
         .filter(|r| sources.contains(&r.source))
+        .cloned()
         .collect()
 }
 
-pub fn fuzzy_filter_in_sources<'a>(
-    records: &Vec<Record<'a>>,
-    sources: &Vec<String>,
-) -> Vec<Record<'a>> {
+pub fn fuzzy_filter_in_sources(records: &[Record], sources: &[String]) -> Vec<Record> {
     records
-        .clone()
-        .into_iter()
+        .iter()
         .filter(|r| {
             sources
                 .iter()
                 .any(|s| r.source.to_lowercase().contains(&s.to_lowercase()))
         })
+        .cloned()
         .collect()
 }
 
-pub const NORMALIZED_CURRENCY: &Currency = iso::PKR;
+/// Which side of transactions to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Only {
+    /// Money going out, along with reversals that offset it
+    Debits,
+    /// Money coming in
+    Credits,
+}
 
-lazy_static! {
-    //FIXME: remove when https://github.com/varunsrin/rusty_money/pull/75 is merged
-    static ref RATES: Vec<ExchangeRate<'static, Currency>> =
-        vec![
-            ExchangeRate::new(iso::USD, iso::PKR, dec!(237)).unwrap(),
-            ExchangeRate::new(iso::SGD, iso::PKR, dec!(158)).unwrap()
-        ];
-    static ref EXCHANGE: Exchange<'static, Currency> = {
-        let mut exchange: Exchange<'static, Currency> = Exchange::new();
-        for rate in RATES.iter() {
-            exchange.set_rate(&rate);
-        }
-        exchange
-    };
+impl Only {
+    pub fn includes(&self, record: &Record) -> bool {
+        (record.nature == Nature::Credit) == (*self == Only::Credits)
+    }
+}
+
+pub fn filter_only(records: &[Record], only: Only) -> Vec<Record> {
+    records
+        .iter()
+        .filter(|r| only.includes(r))
+        .cloned()
+        .collect()
 }
 
-pub fn calculate_total(moneys: &Vec<impl Borrow<Money>>) -> Money {
-    moneys
+/// Totals `records` in the reporting currency, converting each at the rate effective at its time
+pub fn calculate_total(
+    records: &[impl Borrow<Record>],
+    exchange: &Exchange,
+) -> Result<Money, exchange::Error> {
+    let converted = records
         .iter()
-        .map(|r| normalize_amount(r.borrow()).amount().clone())
-        .reduce(|accum, current| accum + current)
-        .map(|total| Money::from_decimal(total, NORMALIZED_CURRENCY))
-        .unwrap_or(Money::from_major(0, iso::USD))
-}
-
-pub fn normalize_amount(amount: &Money) -> Money {
-    //FIXME: remove clone when https://github.com/varunsrin/rusty_money/pull/76 is merged
-    let mut result = amount.clone();
-
-    if amount.currency() != NORMALIZED_CURRENCY {
-        let rate = EXCHANGE
-            .get_rate(amount.currency(), NORMALIZED_CURRENCY)
-            .expect(&format!(
-                "currency rate not configured: {}",
-                amount.currency().iso_alpha_code
-            ));
-
-        result = rate.convert(result).unwrap();
-        let _f = 1;
+        .map(|r| {
+            let r = r.borrow();
+            exchange.convert(&r.amount, &r.time).map(|c| c.amount)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(sum(&converted, exchange.reporting_currency()))
+}
+
+/// Sums amounts that are all in `currency`
+pub fn sum(moneys: &[impl Borrow<Money>], currency: &'static Currency) -> Money {
+    Money::from_decimal(moneys.iter().map(|m| *m.borrow().amount()).sum(), currency)
+}
+
+/// Sums amounts separately for each currency, ordered by ISO code
+pub fn totals_by_currency(moneys: &[impl Borrow<Money>]) -> Vec<Money> {
+    let mut currencies: Vec<_> = moneys.iter().map(|m| m.borrow().currency()).collect();
+    currencies.sort_by_key(|c| c.iso_alpha_code);
+    currencies.dedup();
+
+    currencies
+        .into_iter()
+        .map(|c| {
+            sum(
+                &moneys
+                    .iter()
+                    .map(Borrow::borrow)
+                    .filter(|m: &&Money| m.currency() == c)
+                    .collect::<Vec<_>>(),
+                c,
+            )
+        })
+        .collect()
+}
+
+/// What records are grouped by when aggregating
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Source,
+    Category,
+}
+
+impl GroupBy {
+    pub fn key(&self, record: &Record) -> String {
+        match self {
+            GroupBy::Source => record.source.to_uppercase(),
+            GroupBy::Category => record
+                .category
+                .clone()
+                .unwrap_or_else(|| UNCATEGORIZED.to_string()),
+        }
     }
+}
 
-    result
+pub fn group(records: &[Record]) -> HashMap<String, Vec<Record>> {
+    group_by(records, GroupBy::Source)
 }
 
-pub fn group<'a>(records: &Vec<Record<'a>>) -> HashMap<String, Vec<Record<'a>>> {
+pub fn group_by(records: &[Record], by: GroupBy) -> HashMap<String, Vec<Record>> {
     let mut map = HashMap::new();
 
     for record in records {
-        match map.get_mut(&record.source.to_uppercase()) {
+        match map.get_mut(&by.key(record)) {
             None => {
-                map.insert(record.source.to_uppercase(), vec![record.clone()]);
+                map.insert(by.key(record), vec![record.clone()]);
             }
             Some(list) => list.push(record.clone()),
         };
@@ -105,21 +148,105 @@ pub fn group<'a>(records: &Vec<Record<'a>>) -> HashMap<String, Vec<Record<'a>>>
     map
 }
 
-pub fn group_totals(records: &Vec<Record>) -> HashMap<String, Money> {
-    group(&records)
+pub fn group_totals(
+    records: &[Record],
+    exchange: &Exchange,
+) -> Result<HashMap<String, Money>, exchange::Error> {
+    group(records)
         .into_iter()
-        .map(|(k, v)| (k, calculate_total(&v.iter().map(|r| &r.amount).collect())))
+        .map(|(k, v)| {
+            let total = calculate_total(&v, exchange)?;
+            Ok((k, total))
+        })
         .collect()
 }
 
+/// Length of the periods records are bucketed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Interval {
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+    Year,
+}
+
+impl Interval {
+    /// First day of the period that `date` falls in
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Day => date,
+            Interval::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Interval::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            Interval::Year => NaiveDate::from_ymd(date.year(), 1, 1),
+        }
+    }
+
+    /// First day of the period after the one starting on `start`
+    pub fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Day => start + Duration::days(1),
+            Interval::Week => start + Duration::days(7),
+            Interval::Month => chronoutil::shift_months(start, 1),
+            Interval::Year => chronoutil::shift_years(start, 1),
+        }
+    }
+
+    /// Name of the period starting on `start`
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Interval::Day => start.format("%a, %d/%m/%y").to_string(),
+            Interval::Week => start.format("%G-W%V").to_string(),
+            Interval::Month => start.format("%b %Y").to_string(),
+            Interval::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+/// Buckets records into every period overlapping `start` to `end`, including empty ones
+pub fn group_by_period(
+    records: &[Record],
+    interval: Interval,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<(NaiveDate, Vec<Record>)> {
+    let end = end.naive_utc().date();
+    let mut periods = vec![];
+    let mut cur = interval.start_of(start.naive_utc().date());
+
+    while cur <= end {
+        let next = interval.next(cur);
+        let in_period = records
+            .iter()
+            .filter(|r| {
+                let date = r.time.naive_utc().date();
+                cur <= date && date < next
+            })
+            .cloned()
+            .collect();
+        periods.push((cur, in_period));
+        cur = next;
+    }
+
+    periods
+}
+
 pub struct Subscription {
     pub source: String,
     pub amount: Money,
     pub charge_date: u32,
+    /// Time of the latest charge
+    pub time: DateTime<Utc>,
 }
 
-pub fn get_subscriptions(records: &Vec<Record>) -> Vec<Subscription> {
-    let groups = group(records);
+pub fn get_subscriptions(records: &[Record]) -> Vec<Subscription> {
+    // reversals aren't charges, counting them would break the monthly spacing
+    let charges: Vec<_> = records
+        .iter()
+        .filter(|r| r.nature != Nature::Reversal)
+        .cloned()
+        .collect();
+    let groups = group(&charges);
 
     // filter out groups with only a single charge
     let groups = groups.iter().filter(|(_k, v)| v.len() > 1);
@@ -154,6 +281,116 @@ pub fn get_subscriptions(records: &Vec<Record>) -> Vec<Subscription> {
             source: k.clone(),
             amount: v.last().unwrap().amount.clone(),
             charge_date: v.last().unwrap().time.date().day(),
+            time: v.last().unwrap().time,
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+    use rusty_money::iso;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    fn record(id: u32, time: DateTime<Utc>) -> Record {
+        Record {
+            matcher_id: "paid".to_string(),
+            nature: Nature::Debit,
+            message_id: id,
+            account: "1234".to_string(),
+            amount: Money::from_decimal(Decimal::NEGATIVE_ONE, iso::PKR),
+            source: "Shop".to_string(),
+            time,
+            category: None,
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // 2022-09-19 is a Monday
+        assert_eq!(
+            Interval::Week.start_of(date(2022, 9, 19)),
+            date(2022, 9, 19)
+        );
+        assert_eq!(
+            Interval::Week.start_of(date(2022, 9, 25)),
+            date(2022, 9, 19)
+        );
+        assert_eq!(
+            Interval::Week.start_of(date(2022, 9, 26)),
+            date(2022, 9, 26)
+        );
+        // weeks run across the end of the year
+        assert_eq!(
+            Interval::Week.start_of(date(2023, 1, 1)),
+            date(2022, 12, 26)
+        );
+        assert_eq!(Interval::Week.next(date(2022, 12, 26)), date(2023, 1, 2));
+    }
+
+    #[test]
+    fn months_and_years_roll_over() {
+        assert_eq!(
+            Interval::Month.start_of(date(2022, 12, 31)),
+            date(2022, 12, 1)
+        );
+        assert_eq!(Interval::Month.next(date(2022, 12, 1)), date(2023, 1, 1));
+        assert_eq!(Interval::Month.next(date(2024, 2, 1)), date(2024, 3, 1));
+        assert_eq!(
+            Interval::Year.start_of(date(2022, 12, 31)),
+            date(2022, 1, 1)
+        );
+        assert_eq!(Interval::Year.next(date(2022, 1, 1)), date(2023, 1, 1));
+        assert_eq!(Interval::Day.next(date(2022, 12, 31)), date(2023, 1, 1));
+    }
+
+    #[test]
+    fn groups_records_into_every_period_including_empty_ones() {
+        let records = [
+            record(1, Utc.ymd(2022, 11, 30).and_hms(23, 59, 59)),
+            record(2, Utc.ymd(2023, 1, 1).and_hms(0, 0, 0)),
+            record(3, Utc.ymd(2023, 1, 15).and_hms(12, 0, 0)),
+        ];
+        let periods = group_by_period(
+            &records,
+            Interval::Month,
+            &Utc.ymd(2022, 11, 15).and_hms(0, 0, 0),
+            &Utc.ymd(2023, 1, 10).and_hms(0, 0, 0),
+        );
+
+        let ids: Vec<_> = periods
+            .iter()
+            .map(|(start, records)| {
+                let ids: Vec<_> = records.iter().map(|r| r.message_id).collect();
+                (*start, ids)
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                (date(2022, 11, 1), vec![1]),
+                (date(2022, 12, 1), vec![]),
+                (date(2023, 1, 1), vec![2, 3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_nothing_into_empty_periods() {
+        let periods = group_by_period(
+            &[],
+            Interval::Week,
+            &Utc.ymd(2022, 9, 21).and_hms(0, 0, 0),
+            &Utc.ymd(2022, 9, 27).and_hms(0, 0, 0),
+        );
+        let starts: Vec<_> = periods.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, [date(2022, 9, 19), date(2022, 9, 26)]);
+        assert!(periods.iter().all(|(_, records)| records.is_empty()));
+    }
+}
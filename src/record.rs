@@ -1,30 +1,49 @@
 use chrono::DateTime;
 use chrono::Utc;
 use rusty_money::iso::Currency;
+use serde::Deserialize;
+use serde::Serialize;
 
+use crate::diagnostics::Diagnostics;
+use crate::merchant::MerchantConfig;
 use crate::message::TextMessage;
 use crate::parser::Matcher;
+use crate::parser::Nature;
 use crate::parser::RecordParser;
+use crate::wrapper::money_serde;
 
 pub type Money = rusty_money::Money<'static, Currency>;
 
-#[derive(Debug, Clone)]
-pub struct Record<'a> {
-    pub matcher: &'a Matcher,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub matcher_id: String,
+    pub nature: Nature,
     pub message_id: u32,
     pub account: String,
+    #[serde(with = "money_serde")]
     pub amount: Money,
     pub source: String,
     pub time: DateTime<Utc>,
+    /// Assigned from the configured categories after parsing, see `category::categorize`
+    #[serde(default)]
+    pub category: Option<String>,
 }
 
-impl Record<'_> {
-    pub fn parse_messages<'a>(
-        matchers: &'a Vec<Matcher>,
-        messages: &'a Vec<TextMessage>,
-    ) -> Vec<Record<'a>> {
-        let parser = RecordParser::new(matchers);
+impl Record {
+    /// Parses each of `messages`, with `overlaps` also recording those that more than one
+    /// matcher matched
+    pub fn parse_messages(
+        matchers: &[Matcher],
+        merchants: &MerchantConfig,
+        messages: &[TextMessage],
+        overlaps: bool,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<Record> {
+        let parser = RecordParser::new(matchers, merchants, overlaps);
 
-        messages.iter().filter_map(|m| parser.parse(m)).collect()
+        messages
+            .iter()
+            .filter_map(|m| parser.parse(m, diagnostics))
+            .collect()
     }
 }
@@ -0,0 +1,144 @@
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use quick_xml::events::BytesStart;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::diagnostics::Diagnostics;
+use crate::message;
+use crate::message::open_input;
+use crate::message::Error;
+use crate::message::MessageSource;
+use crate::message::TextMessage;
+
+/// Value of the `type` attribute for messages that were received rather than sent
+const RECEIVED: &str = "1";
+
+/// Reads messages from the XML backups produced by the Android app "SMS Backup & Restore"
+///
+/// The file is streamed one `<sms>` element at a time so large backups are never loaded whole.
+/// Since backups carry no message ids, each message is identified by a hash of its sender,
+/// date and body, so ids don't change when a newer backup adds messages.
+pub struct SmsBackup {
+    path: String,
+}
+
+/// The attributes of an `<sms>` element, any of which may be missing
+struct Sms {
+    address: Option<String>,
+    date: Option<String>,
+    body: Option<String>,
+    kind: Option<String>,
+}
+
+impl Sms {
+    fn id(&self) -> u32 {
+        message::stable_id(&[
+            self.address.as_deref().unwrap_or_default(),
+            self.date.as_deref().unwrap_or_default(),
+            self.body.as_deref().unwrap_or_default(),
+        ])
+    }
+
+    fn time(&self) -> Result<DateTime<Utc>, String> {
+        let date = self.date.as_deref().ok_or("sms without date")?;
+        date.parse()
+            .ok()
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+            .ok_or_else(|| format!("invalid sms date: {}", date))
+    }
+
+    /// Whether the message was received from one of `contacts` between `start` and `end`, as far
+    /// as its attributes tell
+    fn is_wanted(&self, contacts: &[String], start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
+        let received = matches!(self.kind.as_deref(), None | Some(RECEIVED));
+        let from_contact = match &self.address {
+            Some(address) => contacts.contains(address),
+            None => true,
+        };
+        let in_window = match self.time() {
+            Ok(time) => &time >= start && &time <= end,
+            Err(_) => true,
+        };
+
+        received && from_contact && in_window
+    }
+
+    fn to_message(&self) -> Result<TextMessage, String> {
+        Ok(TextMessage {
+            id: self.id(),
+            sender: self.address.clone().ok_or("sms without address")?,
+            text: self.body.clone().ok_or("sms without body")?,
+            time: self.time()?,
+        })
+    }
+}
+
+impl SmsBackup {
+    /// `path` may be `-` to read from stdin
+    pub fn new(path: &str) -> SmsBackup {
+        SmsBackup {
+            path: path.to_string(),
+        }
+    }
+
+    fn parse_sms(element: &BytesStart) -> Result<Sms, Error> {
+        let mut sms = Sms {
+            address: None,
+            date: None,
+            body: None,
+            kind: None,
+        };
+
+        for attr in element.attributes() {
+            let attr = attr.map_err(quick_xml::Error::from)?;
+            let value = Some(attr.unescape_value()?.into_owned());
+            match attr.key.as_ref() {
+                b"address" => sms.address = value,
+                b"date" => sms.date = value,
+                b"body" => sms.body = value,
+                b"type" => sms.kind = value,
+                _ => {}
+            }
+        }
+
+        Ok(sms)
+    }
+}
+
+impl MessageSource for SmsBackup {
+    fn fetch(
+        &self,
+        contacts: &[String],
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<TextMessage>, Error> {
+        let mut reader = Reader::from_reader(open_input(&self.path)?);
+        let mut buf = Vec::new();
+        let mut msgs = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"sms" => {
+                    let sms = SmsBackup::parse_sms(&e)?;
+                    if sms.is_wanted(contacts, start, end) {
+                        match sms.to_message() {
+                            Ok(msg) => msgs.push(msg),
+                            Err(reason) => {
+                                diagnostics.push_unreadable(sms.id(), sms.time().ok(), reason)
+                            }
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        msgs.sort_by_key(|m| m.time);
+        Ok(msgs)
+    }
+}
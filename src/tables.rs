@@ -1,6 +1,9 @@
 use std::fmt::Display;
 use std::vec;
 
+use chrono::DateTime;
+use chrono::Utc;
+use clap::ValueEnum;
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::modifiers::UTF8_SOLID_INNER_BORDERS;
 use comfy_table::presets::UTF8_FULL;
@@ -11,38 +14,212 @@ use comfy_table::Color;
 use comfy_table::ContentArrangement;
 use comfy_table::Row;
 use comfy_table::Table;
+use rusty_money::iso;
 
+use crate::budget::BudgetStatus;
+use crate::diagnostics::Diagnostics;
+use crate::diagnostics::Problem;
+use crate::examples::ExampleResult;
+use crate::exchange;
+use crate::exchange::Conversion;
+use crate::exchange::Exchange;
 use crate::process;
+use crate::process::GroupBy;
+use crate::process::Interval;
+use crate::process::Only;
 use crate::record::Money;
 use crate::record::Record;
 use crate::Subscription;
 
-fn create_total_row(total: &Money, col_count: usize) -> Row {
-    if col_count < 2 {
-        panic!("table must have atleast 2 cols")
+fn create_total_row(totals: &[Money], col_count: usize) -> Row {
+    create_summary_row("TOTAL", totals, col_count)
+}
+
+fn create_summary_row(label: &str, totals: &[Money], col_count: usize) -> Row {
+    if col_count < totals.len() + 1 {
+        panic!("table must have atleast {} cols", totals.len() + 1)
     }
 
-    let empty_rows = col_count - 2;
+    let empty_rows = col_count - totals.len() - 1;
     let mut row = Row::new();
 
     for _ in 0..empty_rows {
         row.add_cell(Cell::new(""));
     }
     row.add_cell(
-        Cell::new("TOTAL")
+        Cell::new(label)
             .add_attributes(vec![Attribute::Bold])
             .set_alignment(CellAlignment::Right),
     );
 
-    row.add_cell(Cell::new(&total).fg(if total.amount().is_sign_positive() {
-        Color::Green
-    } else {
-        Color::Red
-    }));
+    for total in totals {
+        row.add_cell(Cell::new(total).fg(if total.amount().is_sign_positive() {
+            Color::Green
+        } else {
+            Color::Red
+        }));
+    }
 
     row
 }
 
+/// How amounts in different currencies are shown and totalled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TotalsMode {
+    /// Convert everything into the reporting currency
+    #[default]
+    Converted,
+    /// Show a column per currency without converting anything
+    ByCurrency,
+    /// Show a column per currency along with a converted grand total
+    Both,
+}
+
+impl TotalsMode {
+    pub fn converts(&self) -> bool {
+        *self != TotalsMode::ByCurrency
+    }
+}
+
+/// The amount columns of a table, either a single converted one or one per currency
+struct AmountColumns<'a> {
+    mode: TotalsMode,
+    currencies: Vec<&'static iso::Currency>,
+    exchange: &'a Exchange,
+}
+
+impl<'a> AmountColumns<'a> {
+    fn new<'b>(
+        mode: TotalsMode,
+        exchange: &'a Exchange,
+        amounts: impl IntoIterator<Item = &'b Money>,
+    ) -> AmountColumns<'a> {
+        let mut currencies: Vec<_> = amounts.into_iter().map(|m| m.currency()).collect();
+        currencies.sort_by_key(|c| c.iso_alpha_code);
+        currencies.dedup();
+
+        AmountColumns {
+            mode,
+            currencies,
+            exchange,
+        }
+    }
+
+    fn headers(&self, label: &str) -> Vec<String> {
+        let converted = format!(
+            "{} ({})",
+            label,
+            self.exchange.reporting_currency().iso_alpha_code
+        );
+        let mut headers: Vec<_> = self
+            .currencies
+            .iter()
+            .map(|c| c.iso_alpha_code.to_string())
+            .collect();
+
+        match self.mode {
+            TotalsMode::Converted => vec![label.to_string()],
+            TotalsMode::ByCurrency => headers,
+            TotalsMode::Both => {
+                headers.push(converted);
+                headers
+            }
+        }
+    }
+
+    /// Headers for one of several groups of amount columns, per currency ones are prefixed
+    /// with `label`
+    fn section_headers(&self, label: &str) -> Vec<String> {
+        match self.mode {
+            TotalsMode::Converted => self.headers(label),
+            _ => self
+                .headers(label)
+                .into_iter()
+                .zip(self.currencies.iter().map(Some).chain([None]))
+                .map(|(header, currency)| match currency {
+                    Some(_) => format!("{} {}", label, header),
+                    None => header,
+                })
+                .collect(),
+        }
+    }
+
+    /// Places each of `amounts` in the column of its currency, `converted` is only shown if the
+    /// mode converts
+    fn cells(&self, amounts: &[Money], converted: Option<&Money>) -> Vec<Cell> {
+        let mut cells: Vec<_> = self
+            .currencies
+            .iter()
+            .map(|c| match amounts.iter().find(|m| m.currency() == *c) {
+                Some(amount) => Cell::new(amount),
+                None => Cell::new(""),
+            })
+            .collect();
+        let converted = Cell::new(converted.map(ToString::to_string).unwrap_or_default());
+
+        match self.mode {
+            TotalsMode::Converted => vec![converted],
+            TotalsMode::ByCurrency => cells,
+            TotalsMode::Both => {
+                cells.push(converted);
+                cells
+            }
+        }
+    }
+
+    /// Totals of `records` in each currency, and converted if the mode converts
+    fn summarize(
+        &self,
+        records: &[Record],
+    ) -> Result<(Vec<Money>, Option<Money>), exchange::Error> {
+        let converted = if self.mode.converts() {
+            Some(process::calculate_total(records, self.exchange)?)
+        } else {
+            None
+        };
+        let amounts: Vec<_> = records.iter().map(|r| &r.amount).collect();
+
+        Ok((process::totals_by_currency(&amounts), converted))
+    }
+
+    fn totals(&self, amounts: &[Money], converted: Option<Money>) -> Vec<Money> {
+        let by_currency = self.currencies.iter().map(|c| {
+            process::sum(
+                &amounts
+                    .iter()
+                    .filter(|m| m.currency() == *c)
+                    .collect::<Vec<_>>(),
+                c,
+            )
+        });
+        let converted = converted.into_iter();
+
+        match self.mode {
+            TotalsMode::Converted => converted.collect(),
+            TotalsMode::ByCurrency => by_currency.collect(),
+            TotalsMode::Both => by_currency.chain(converted).collect(),
+        }
+    }
+}
+
+/// Describes the rate an amount was converted at, empty if it wasn't converted
+fn format_rate(amount: &Money, conversion: &Conversion) -> String {
+    if amount.currency() == conversion.amount.currency() {
+        return String::new();
+    }
+
+    let rate = format!(
+        "1 {} = {} {}",
+        amount.currency().iso_alpha_code,
+        conversion.rate.round_dp(4).normalize(),
+        conversion.amount.currency().iso_alpha_code
+    );
+    match conversion.rate_date {
+        Some(date) => format!("{} ({})", rate, date),
+        None => rate,
+    }
+}
+
 fn default_table() -> Table {
     let mut table = Table::new();
     table
@@ -55,138 +232,576 @@ fn default_table() -> Table {
 
 pub struct TransactionsTable<'a> {
     show_matchers: bool,
-    records: &'a Vec<Record<'a>>,
+    show_rates: bool,
+    records: &'a Vec<Record>,
+    exchange: &'a Exchange,
+    mode: TotalsMode,
 }
 
 impl<'a> TransactionsTable<'a> {
-    pub fn new(records: &'a Vec<Record>, show_matchers: bool) -> TransactionsTable<'a> {
+    pub fn new(
+        records: &'a Vec<Record>,
+        exchange: &'a Exchange,
+        mode: TotalsMode,
+        show_matchers: bool,
+    ) -> TransactionsTable<'a> {
         TransactionsTable {
             records,
+            exchange,
+            mode,
             show_matchers,
+            show_rates: mode.converts()
+                && records
+                    .iter()
+                    .any(|r| r.amount.currency() != exchange.reporting_currency()),
         }
     }
 
-    fn record_to_row(&self, r: &Record) -> Row {
+    fn record_to_row(&self, r: &Record, columns: &AmountColumns) -> Result<Row, std::fmt::Error> {
         let mut row = Row::new();
         row.add_cell(Cell::new(r.message_id));
         row.add_cell(Cell::new(r.time.format("%a, %d/%m/%y %I:%M %p")));
         if self.show_matchers {
-            row.add_cell(Cell::new(&r.matcher.id));
+            row.add_cell(Cell::new(&r.matcher_id));
         }
         row.add_cell(Cell::new(&r.source));
-        row.add_cell(Cell::new(process::normalize_amount(&r.amount)).fg(
-            if r.amount.is_positive() {
-                Color::Green
-            } else {
-                Color::Red
-            },
-        ));
 
-        row
+        let conversion = if self.mode.converts() {
+            Some(
+                self.exchange
+                    .convert(&r.amount, &r.time)
+                    .map_err(|_| std::fmt::Error)?,
+            )
+        } else {
+            None
+        };
+        if let (true, Some(conversion)) = (self.show_rates, &conversion) {
+            row.add_cell(Cell::new(format_rate(&r.amount, conversion)));
+        }
+
+        let amount = columns.cells(
+            std::slice::from_ref(&r.amount),
+            conversion.as_ref().map(|c| &c.amount),
+        );
+        let empty = columns.cells(&[], None);
+        let (income, expense) = if Only::Credits.includes(r) {
+            (amount, empty)
+        } else {
+            (empty, amount)
+        };
+        let color = if r.amount.is_positive() {
+            Color::Green
+        } else {
+            Color::Red
+        };
+        for cell in income.into_iter().chain(expense) {
+            row.add_cell(cell.fg(color));
+        }
+
+        Ok(row)
     }
 }
 
 impl Display for TransactionsTable<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let header = if self.show_matchers {
-            vec!["ID", "Time", "Pattern", "Reason", "Amount"]
-        } else {
-            vec!["ID", "Time", "Reason", "Amount"]
-        };
+        let columns = AmountColumns::new(
+            self.mode,
+            self.exchange,
+            self.records.iter().map(|r| &r.amount),
+        );
+
+        let mut header = vec!["ID".to_string(), "Time".to_string()];
+        if self.show_matchers {
+            header.push("Pattern".to_string());
+        }
+        header.push("Reason".to_string());
+        if self.show_rates {
+            header.push("Rate".to_string());
+        }
+        header.extend(columns.section_headers("Income"));
+        header.extend(columns.section_headers("Expenses"));
 
         let col_count = header.len();
 
+        let mut totals = vec![];
+        for only in [Only::Credits, Only::Debits] {
+            let (amounts, converted) = columns
+                .summarize(&process::filter_only(self.records, only))
+                .map_err(|_| std::fmt::Error)?;
+            totals.extend(columns.totals(&amounts, converted));
+        }
+        let (amounts, converted) = columns
+            .summarize(self.records)
+            .map_err(|_| std::fmt::Error)?;
+        let net = columns.totals(&amounts, converted);
+
         let mut table = default_table();
         table
             .set_header(header)
             .add_rows(
                 self.records
                     .iter()
-                    .map(|r| self.record_to_row(r))
-                    .collect::<Vec<_>>(),
+                    .map(|r| self.record_to_row(r, &columns))
+                    .collect::<Result<Vec<_>, _>>()?,
             )
-            .add_row(create_total_row(
-                &process::calculate_total(&self.records.iter().map(|r| &r.amount).collect()),
-                col_count,
-            ));
+            .add_row(create_total_row(&totals, col_count))
+            .add_row(create_summary_row("NET", &net, col_count));
 
         table.fmt(f)
     }
 }
 
 pub struct TotalsTable<'a> {
-    records: &'a Vec<Record<'a>>,
+    records: &'a Vec<Record>,
+    exchange: &'a Exchange,
+    mode: TotalsMode,
+    group_by: GroupBy,
 }
 
 impl<'a> TotalsTable<'a> {
-    pub fn new(records: &'a Vec<Record<'a>>) -> TotalsTable<'a> {
-        TotalsTable { records }
+    pub fn new(
+        records: &'a Vec<Record>,
+        exchange: &'a Exchange,
+        mode: TotalsMode,
+        group_by: GroupBy,
+    ) -> TotalsTable<'a> {
+        TotalsTable {
+            records,
+            exchange,
+            mode,
+            group_by,
+        }
     }
 }
 
 impl Display for TotalsTable<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut totals: Vec<_> = process::group_totals(&self.records).into_iter().collect();
-        totals.sort_by(|a, b| a.1.cmp(&b.1));
+        let columns = AmountColumns::new(
+            self.mode,
+            self.exchange,
+            self.records.iter().map(|r| &r.amount),
+        );
+        // income, expenses and net of a set of records
+        let sections = |records: &[Record]| {
+            [
+                columns.summarize(&process::filter_only(records, Only::Credits)),
+                columns.summarize(&process::filter_only(records, Only::Debits)),
+                columns.summarize(records),
+            ]
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+        };
+
+        let mut totals: Vec<_> = process::group_by(self.records, self.group_by)
+            .into_iter()
+            .map(|(k, v)| Ok((k, sections(&v)?)))
+            .collect::<Result<_, exchange::Error>>()
+            .map_err(|_| std::fmt::Error)?;
+        totals.sort_by(|a, b| a.1[2].1.cmp(&b.1[2].1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut header = vec![match self.group_by {
+            GroupBy::Source => "Source".to_string(),
+            GroupBy::Category => "Category".to_string(),
+        }];
+        header.extend(columns.section_headers("Income"));
+        header.extend(columns.section_headers("Expenses"));
+        header.extend(columns.section_headers("Net"));
+        let col_count = header.len();
+
+        let grand_totals: Vec<_> = sections(self.records)
+            .map_err(|_| std::fmt::Error)?
+            .into_iter()
+            .flat_map(|(amounts, converted)| columns.totals(&amounts, converted))
+            .collect();
 
         let mut table = default_table();
         table
-            .set_header(vec!["Source", "Total"])
+            .set_header(header)
             .add_rows(
                 totals
                     .iter()
-                    .map(|(k, v)| vec![Cell::new(k), Cell::new(v)])
+                    .map(|(k, sections)| {
+                        let mut row = vec![Cell::new(k)];
+                        for (amounts, converted) in sections {
+                            row.extend(columns.cells(amounts, converted.as_ref()));
+                        }
+                        row
+                    })
                     .collect::<Vec<_>>(),
             )
-            .add_row(create_total_row(
-                &process::calculate_total(&totals.iter().map(|(_k, v)| v).collect()),
-                2,
-            ));
+            .add_row(create_total_row(&grand_totals, col_count));
 
         table.fmt(f)
     }
 }
 
 pub struct SubscriptionsTable<'a> {
-    records: &'a Vec<Record<'a>>,
+    records: &'a Vec<Record>,
+    exchange: &'a Exchange,
+    mode: TotalsMode,
 }
 
 impl<'a> SubscriptionsTable<'a> {
-    pub fn new(records: &'a Vec<Record<'a>>) -> SubscriptionsTable<'a> {
-        SubscriptionsTable { records }
+    pub fn new(
+        records: &'a Vec<Record>,
+        exchange: &'a Exchange,
+        mode: TotalsMode,
+    ) -> SubscriptionsTable<'a> {
+        SubscriptionsTable {
+            records,
+            exchange,
+            mode,
+        }
     }
 }
 
 impl Display for SubscriptionsTable<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut subs: Vec<_> = process::get_subscriptions(&self.records)
+        let mut subs: Vec<(Subscription, Option<Money>)> = process::get_subscriptions(self.records)
             .into_iter()
-            .map(|s| Subscription {
-                amount: process::normalize_amount(&s.amount),
-                ..s
+            .map(|s| {
+                let converted = if self.mode.converts() {
+                    Some(self.exchange.convert(&s.amount, &s.time)?.amount)
+                } else {
+                    None
+                };
+                Ok((s, converted))
             })
-            .collect();
-        subs.sort_by(|a, b| a.amount.cmp(&b.amount));
+            .collect::<Result<_, exchange::Error>>()
+            .map_err(|_| std::fmt::Error)?;
+        subs.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.source.cmp(&b.0.source)));
+
+        let columns = AmountColumns::new(
+            self.mode,
+            self.exchange,
+            subs.iter().map(|(s, _c)| &s.amount),
+        );
+        let mut header = vec!["Source".to_string(), "Day".to_string()];
+        header.extend(columns.headers("Amount"));
+        let col_count = header.len();
+
+        let amounts: Vec<_> = subs.iter().map(|(s, _c)| s.amount.clone()).collect();
+        let converted = self.mode.converts().then(|| {
+            process::sum(
+                &subs.iter().flat_map(|(_s, c)| c).collect::<Vec<_>>(),
+                self.exchange.reporting_currency(),
+            )
+        });
 
         let mut table = default_table();
         table
-            .set_header(vec!["Source", "Day", "Amount"])
+            .set_header(header)
             .add_rows(
                 subs.iter()
-                    .map(|s| {
-                        vec![
-                            Cell::new(&s.source),
-                            Cell::new(s.charge_date),
-                            Cell::new(&s.amount),
-                        ]
+                    .map(|(s, c)| {
+                        let mut row = vec![Cell::new(&s.source), Cell::new(s.charge_date)];
+                        row.extend(columns.cells(std::slice::from_ref(&s.amount), c.as_ref()));
+                        row
                     })
                     .collect::<Vec<_>>(),
             )
             .add_row(create_total_row(
-                &process::calculate_total(&subs.iter().map(|s| &s.amount).collect()),
-                3,
+                &columns.totals(&amounts, converted),
+                col_count,
             ));
 
         table.fmt(f)
     }
 }
+
+pub struct BudgetTable<'a> {
+    statuses: &'a [BudgetStatus],
+}
+
+impl<'a> BudgetTable<'a> {
+    pub fn new(statuses: &'a [BudgetStatus]) -> BudgetTable<'a> {
+        BudgetTable { statuses }
+    }
+}
+
+impl Display for BudgetTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = default_table();
+        table
+            .set_header(vec![
+                "Budget",
+                "Period",
+                "Amount",
+                "Spent",
+                "Remaining",
+                "Used",
+            ])
+            .add_rows(self.statuses.iter().map(|s| {
+                let color = if s.is_over() {
+                    Color::Red
+                } else {
+                    Color::Green
+                };
+                let used = s
+                    .percent_used()
+                    .map(|p| format!("{}%", p))
+                    .unwrap_or_default();
+
+                vec![
+                    Cell::new(&s.target),
+                    Cell::new(format!("{} to {}", s.start, s.end)),
+                    Cell::new(&s.limit),
+                    Cell::new(&s.spent),
+                    Cell::new(s.remaining()).fg(color),
+                    Cell::new(used).fg(color),
+                ]
+            }));
+
+        table.fmt(f)
+    }
+}
+
+fn money_cell(amount: &Money) -> Cell {
+    Cell::new(amount).fg(if amount.is_negative() {
+        Color::Red
+    } else {
+        Color::Green
+    })
+}
+
+pub struct PeriodsTable<'a> {
+    records: &'a [Record],
+    exchange: &'a Exchange,
+    interval: Interval,
+    start: &'a DateTime<Utc>,
+    end: &'a DateTime<Utc>,
+    pivot: Option<GroupBy>,
+}
+
+impl<'a> PeriodsTable<'a> {
+    pub fn new(
+        records: &'a [Record],
+        exchange: &'a Exchange,
+        interval: Interval,
+        start: &'a DateTime<Utc>,
+        end: &'a DateTime<Utc>,
+        pivot: Option<GroupBy>,
+    ) -> PeriodsTable<'a> {
+        PeriodsTable {
+            records,
+            exchange,
+            interval,
+            start,
+            end,
+            pivot,
+        }
+    }
+
+    /// Debits, credits and net of `records`, reversals count towards debits
+    fn sides(&self, records: &[Record]) -> Result<[Money; 3], exchange::Error> {
+        Ok([
+            process::calculate_total(&process::filter_only(records, Only::Debits), self.exchange)?,
+            process::calculate_total(&process::filter_only(records, Only::Credits), self.exchange)?,
+            process::calculate_total(records, self.exchange)?,
+        ])
+    }
+
+    /// Count, debits, credits and net of each period
+    fn summary(&self, periods: &[(String, Vec<Record>)]) -> Result<Table, exchange::Error> {
+        let mut table = default_table();
+        table.set_header(vec!["Period", "Count", "Debits", "Credits", "Net"]);
+
+        for (label, records) in periods {
+            let mut row = vec![Cell::new(label), Cell::new(records.len())];
+            row.extend(self.sides(records)?.iter().map(money_cell));
+            table.add_row(row);
+        }
+
+        let records: Vec<_> = periods.iter().flat_map(|(_l, v)| v.clone()).collect();
+        table.add_row(create_total_row(&self.sides(&records)?, 5));
+
+        Ok(table)
+    }
+
+    /// Net of each group in each period, with a column per group
+    fn pivoted(
+        &self,
+        periods: &[(String, Vec<Record>)],
+        by: GroupBy,
+    ) -> Result<Table, exchange::Error> {
+        let currency = self.exchange.reporting_currency();
+        let mut keys: Vec<_> = process::group_by(self.records, by).into_keys().collect();
+        keys.sort();
+
+        let mut header = vec!["Period".to_string()];
+        header.extend(keys.iter().cloned());
+        header.push("Net".to_string());
+        let col_count = header.len();
+
+        let mut table = default_table();
+        table.set_header(header);
+
+        let mut totals = vec![vec![]; keys.len() + 1];
+        for (label, records) in periods {
+            let groups = process::group_by(records, by);
+            let mut row = vec![Cell::new(label)];
+
+            for (i, key) in keys.iter().enumerate() {
+                let net = match groups.get(key) {
+                    Some(v) => process::calculate_total(v, self.exchange)?,
+                    None => Money::from_decimal(0.into(), currency),
+                };
+                row.push(money_cell(&net));
+                totals[i].push(net);
+            }
+
+            let net = process::calculate_total(records, self.exchange)?;
+            row.push(money_cell(&net));
+            totals[keys.len()].push(net);
+            table.add_row(row);
+        }
+
+        table.add_row(create_total_row(
+            &totals
+                .iter()
+                .map(|v| process::sum(v, currency))
+                .collect::<Vec<_>>(),
+            col_count,
+        ));
+
+        Ok(table)
+    }
+}
+
+impl Display for PeriodsTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let periods: Vec<_> =
+            process::group_by_period(self.records, self.interval, self.start, self.end)
+                .into_iter()
+                .map(|(start, records)| (self.interval.label(start), records))
+                .collect();
+
+        let table = match self.pivot {
+            Some(by) => self.pivoted(&periods, by),
+            None => self.summary(&periods),
+        }
+        .map_err(|_| std::fmt::Error)?;
+
+        table.fmt(f)
+    }
+}
+
+pub struct DiagnosticsTable<'a> {
+    diagnostics: &'a Diagnostics,
+}
+
+impl<'a> DiagnosticsTable<'a> {
+    pub fn new(diagnostics: &'a Diagnostics) -> DiagnosticsTable<'a> {
+        DiagnosticsTable { diagnostics }
+    }
+}
+
+impl Display for DiagnosticsTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = default_table();
+        table
+            .set_header(vec![
+                "ID", "Received", "Problem", "Pattern", "Field", "Error", "Message",
+            ])
+            .add_rows(self.diagnostics.iter().map(|d| {
+                let (problem, matcher_id, field, error) = match &d.problem {
+                    Problem::Unreadable { reason } => ("Unreadable", "", "", reason.clone()),
+                    Problem::Unmatched => ("No match", "", "", String::new()),
+                    Problem::Failed {
+                        matcher_id,
+                        field,
+                        error,
+                    } => ("Failed", matcher_id.as_str(), field.as_str(), error.clone()),
+                    Problem::ImplausibleTime { matcher_id, time } => (
+                        "Suspicious time",
+                        matcher_id.as_str(),
+                        "time",
+                        format!("read as {}", time.format("%a, %d/%m/%y %I:%M %p")),
+                    ),
+                    Problem::Overlap { matcher_id, others } => (
+                        "Overlap",
+                        matcher_id.as_str(),
+                        "",
+                        format!("also matched by {}", others.join(", ")),
+                    ),
+                };
+
+                vec![
+                    Cell::new(d.message_id),
+                    Cell::new(
+                        d.received
+                            .map(|t| t.format("%a, %d/%m/%y %I:%M %p").to_string())
+                            .unwrap_or_default(),
+                    ),
+                    Cell::new(problem).fg(match d.problem {
+                        Problem::ImplausibleTime { .. } | Problem::Overlap { .. } => Color::Yellow,
+                        _ => Color::Red,
+                    }),
+                    Cell::new(matcher_id),
+                    Cell::new(field),
+                    Cell::new(error),
+                    Cell::new(&d.text),
+                ]
+            }));
+
+        table.fmt(f)
+    }
+}
+
+pub struct ExamplesTable<'a> {
+    results: &'a [ExampleResult],
+}
+
+impl<'a> ExamplesTable<'a> {
+    pub fn new(results: &'a [ExampleResult]) -> ExamplesTable<'a> {
+        ExamplesTable { results }
+    }
+}
+
+impl Display for ExamplesTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = default_table();
+        table.set_header(vec![
+            "Pattern", "Example", "Result", "Field", "Expected", "Actual",
+        ]);
+
+        for result in self.results {
+            let example = vec![
+                Cell::new(&result.matcher_id),
+                Cell::new(format!("#{} {}", result.number, result.text)),
+            ];
+
+            if !result.overlaps.is_empty() {
+                let mut row = example.clone();
+                row.extend(vec![
+                    Cell::new("WARN").fg(Color::Yellow),
+                    Cell::new("matcher"),
+                    Cell::new(&result.matcher_id),
+                    Cell::new(format!("also matched by {}", result.overlaps.join(", ")))
+                        .fg(Color::Yellow),
+                ]);
+                table.add_row(row);
+            }
+
+            if result.passed() {
+                let mut row = example;
+                row.push(Cell::new("PASS").fg(Color::Green));
+                table.add_row(row);
+                continue;
+            }
+
+            for mismatch in &result.mismatches {
+                let mut row = example.clone();
+                row.extend(vec![
+                    Cell::new("FAIL").fg(Color::Red),
+                    Cell::new(&mismatch.field),
+                    Cell::new(&mismatch.expected),
+                    Cell::new(&mismatch.actual).fg(Color::Red),
+                ]);
+                table.add_row(row);
+            }
+        }
+
+        table.fmt(f)
+    }
+}
@@ -0,0 +1,112 @@
+/// Recovers the plain text of an `NSAttributedString` archived in the `typedstream` format, as
+/// stored by the Messages app in `message.attributedBody`
+///
+/// Only the string contents are decoded, attributes such as links and mentions are ignored.
+/// Returns `None` if the blob doesn't contain a string that can be decoded.
+pub fn decode_attributed_string(blob: &[u8]) -> Option<String> {
+    let class_end = [b"NSString".as_slice(), b"NSMutableString".as_slice()]
+        .iter()
+        .find_map(|class| find(blob, class).map(|i| i + class.len()))?;
+
+    // the class name is followed by a few bytes of type information and then `+`, the type
+    // encoding of the string's bytes, which precedes their length and the bytes themselves
+    let rest = &blob[class_end..];
+    let start = rest.iter().take(16).position(|b| *b == b'+')? + 1;
+    let (len, offset) = read_length(&rest[start..])?;
+    let bytes = rest.get(start + offset..start + offset + len)?;
+
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Reads a length which is either a single byte or a `0x81`/`0x82` tag followed by a little
+/// endian `u16`/`u32`, returning the length and the number of bytes it took up
+fn read_length(bytes: &[u8]) -> Option<(usize, usize)> {
+    match *bytes.first()? {
+        0x81 => Some((
+            u16::from_le_bytes(bytes.get(1..3)?.try_into().ok()?) as usize,
+            3,
+        )),
+        0x82 => Some((
+            u32::from_le_bytes(bytes.get(1..5)?.try_into().ok()?) as usize,
+            5,
+        )),
+        len if len < 0x80 => Some((len as usize, 1)),
+        _ => None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An archived `NSAttributedString` as the Messages app writes it, holding `text`
+    fn archive(text: &str) -> Vec<u8> {
+        let mut blob = b"\x04\x0bstreamtyped\x81\xe8\x03\x84\x01@\x84\x84\x84\x12NSAttributedString\x00\x84\x84\x08NSObject\x00\x85\x92\x84\x84\x84\x08NSString\x01\x94\x84\x01+".to_vec();
+        let len = text.len();
+        if len < 0x80 {
+            blob.push(len as u8);
+        } else if len <= u16::MAX as usize {
+            blob.push(0x81);
+            blob.extend((len as u16).to_le_bytes());
+        } else {
+            blob.push(0x82);
+            blob.extend((len as u32).to_le_bytes());
+        }
+        blob.extend(text.as_bytes());
+        blob.extend(
+            b"\x86\x84\x02iI\x01\x05\x92\x84\x84\x84\x0cNSDictionary\x00\x94\x84\x01i\x00\x86\x86",
+        );
+        blob
+    }
+
+    #[test]
+    fn decodes_short_string() {
+        let text = "Your account was charged 12.50";
+        assert_eq!(
+            decode_attributed_string(&archive(text)).as_deref(),
+            Some(text)
+        );
+    }
+
+    #[test]
+    fn decodes_string_with_two_byte_length() {
+        let text = "Café ".repeat(40);
+        assert!(text.len() >= 0x80);
+        assert_eq!(decode_attributed_string(&archive(&text)), Some(text));
+    }
+
+    #[test]
+    fn decodes_string_with_four_byte_length() {
+        let text = "x".repeat(70_000);
+        assert_eq!(decode_attributed_string(&archive(&text)), Some(text));
+    }
+
+    #[test]
+    fn decodes_mutable_string() {
+        let blob = archive("hello");
+        let i = find(&blob, b"\x08NSString").unwrap();
+        let mut mutable = blob[..i].to_vec();
+        mutable.extend(b"\x0fNSMutableString");
+        mutable.extend(&blob[i + 9..]);
+        assert_eq!(decode_attributed_string(&mutable).as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn rejects_blob_without_string() {
+        let blob = b"\x04\x0bstreamtyped\x81\xe8\x03\x84\x01@\x84\x84\x84\x08NSNumber\x00\x84\x01i\x05\x86";
+        assert_eq!(decode_attributed_string(blob), None);
+    }
+
+    #[test]
+    fn rejects_truncated_string() {
+        let blob = archive("a longer message that gets cut off");
+        let i = find(&blob, b"cut off").unwrap();
+        assert_eq!(decode_attributed_string(&blob[..i]), None);
+    }
+}
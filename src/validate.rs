@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use serde::de::DeserializeOwned;
+use serde_path_to_error::Segment;
+use yaml_rust::parser::Event;
+use yaml_rust::parser::MarkedEventReceiver;
+use yaml_rust::parser::Parser;
+use yaml_rust::scanner::Marker;
+
+use crate::budget::Budget;
+use crate::budget::Period;
+use crate::config::Config;
+use crate::parser::DateTimeParser;
+use crate::parser::Matcher;
+use crate::parser::NatureConfig;
+use crate::parser::TimeValue;
+use crate::parser::Value;
+use crate::parser::ValueParser;
+
+/// A mistake in the config, found before it is used
+#[derive(Debug)]
+pub struct Problem {
+    /// Line and column in the YAML file
+    pub location: Option<(usize, usize)>,
+    pub matcher_id: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "line {}, column {}: ", line, column)?;
+        }
+        if let Some(id) = &self.matcher_id {
+            write!(f, "matcher {}: ", id)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+/// Where each key and sequence item of a YAML document is, by dot separated path like
+/// `matchers.0.values.time`
+#[derive(Default)]
+struct Locations {
+    map: HashMap<String, (usize, usize)>,
+    stack: Vec<Frame>,
+}
+
+impl Locations {
+    fn of(yaml: &str) -> Locations {
+        let mut locations = Locations::default();
+        // syntax errors are reported when the YAML is converted
+        let _ = Parser::new(yaml.chars()).load(&mut locations, false);
+        locations
+    }
+
+    fn join(path: &str, segment: &str) -> String {
+        if path.is_empty() {
+            segment.to_string()
+        } else {
+            format!("{}.{}", path, segment)
+        }
+    }
+
+    /// Path of the next value in the innermost mapping or sequence
+    fn next_value(&mut self, mark: Marker) -> String {
+        match self.stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Mapping { path, key }) => {
+                Locations::join(path, &key.take().unwrap_or_default())
+            }
+            Some(Frame::Sequence { path, index }) => {
+                let path = Locations::join(path, &index.to_string());
+                *index += 1;
+                self.map.insert(path.clone(), (mark.line(), mark.col() + 1));
+                path
+            }
+        }
+    }
+
+    /// Location of `path`, or of its closest parent that has one
+    fn find(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+        loop {
+            if let Some(location) = self.map.get(path) {
+                return Some(*location);
+            }
+            path = &path[..path.rfind('.')?];
+        }
+    }
+}
+
+impl MarkedEventReceiver for Locations {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => match self.stack.last_mut() {
+                Some(Frame::Mapping {
+                    path,
+                    key: key @ None,
+                }) => {
+                    self.map
+                        .insert(Locations::join(path, &value), (mark.line(), mark.col() + 1));
+                    *key = Some(value);
+                }
+                _ => {
+                    self.next_value(mark);
+                }
+            },
+            Event::MappingStart(_) => {
+                let path = self.next_value(mark);
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.next_value(mark);
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            Event::Alias(_) => {
+                self.next_value(mark);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Collects the problems of one matcher
+struct MatcherCheck<'a> {
+    path: String,
+    id: &'a str,
+    /// Names of the capture groups in the pattern
+    groups: HashSet<&'a str>,
+    locations: &'a Locations,
+    problems: &'a mut Vec<Problem>,
+}
+
+impl MatcherCheck<'_> {
+    fn report(&mut self, path: &str, message: String) {
+        self.problems.push(Problem {
+            location: self.locations.find(&Locations::join(&self.path, path)),
+            matcher_id: Some(self.id.to_string()),
+            message,
+        });
+    }
+
+    fn check_groups(&mut self, path: &str, group: &str, fallback: &[String]) {
+        let used = std::iter::once((format!("{}.config.group", path), group)).chain(
+            fallback
+                .iter()
+                .enumerate()
+                .map(|(i, group)| (format!("{}.config.fallback.{}", path, i), group.as_str())),
+        );
+
+        let missing: Vec<_> = used
+            .filter(|(_, group)| !self.groups.contains(group))
+            .collect();
+        for (path, group) in missing {
+            self.report(&path, format!("group {} is not in the pattern", group));
+        }
+    }
+
+    fn check_value<T: Clone, R: ValueParser<T>>(&mut self, path: &str, value: &Value<T, R>) {
+        if let Value::FromMatch {
+            group, fallback, ..
+        } = value
+        {
+            self.check_groups(path, group, fallback);
+        }
+    }
+
+    fn check_format(&mut self, path: &str, format: &str) {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            self.report(path, format!("{} is not a valid date format", format));
+        }
+    }
+
+    fn check_formats(&mut self, path: &str, formats: &[String]) {
+        if formats.is_empty() {
+            self.report(path, "at least one date format is needed".to_string());
+        }
+        for (i, format) in formats.iter().enumerate() {
+            self.check_format(&format!("{}.formats.{}", path, i), format);
+        }
+    }
+
+    fn check_time(&mut self, time: &TimeValue) {
+        match time {
+            TimeValue::FromMatch {
+                group,
+                parser,
+                fallback,
+                ..
+            } => {
+                self.check_groups("values.time", group, fallback);
+                let path = "values.time.config.parser.config";
+                match parser {
+                    DateTimeParser::FormattedDateTime(format) => self.check_format(path, format),
+                    DateTimeParser::FormattedDateTimeWithAppend { format, .. } => {
+                        self.check_format(&format!("{}.format", path), format)
+                    }
+                    DateTimeParser::Formatted { formats, .. } => self.check_formats(path, formats),
+                }
+            }
+            TimeValue::DateFromMatch {
+                group,
+                formats,
+                fallback,
+                ..
+            } => {
+                self.check_groups("values.time", group, fallback);
+                self.check_formats("values.time.config", formats);
+            }
+            TimeValue::Fixed(_) | TimeValue::MessageTime => {}
+        }
+    }
+
+    fn check(&mut self, matcher: &Matcher) {
+        let values = &matcher.values;
+        self.check_value("values.account", &values.account);
+        self.check_value("values.amount", &values.amount);
+        self.check_value("values.currency", &values.currency);
+        self.check_value("values.source", &values.source);
+        self.check_time(&values.time);
+        if let NatureConfig::Value(nature) = &matcher.nature {
+            self.check_value("nature", nature);
+        }
+
+        for (i, example) in matcher.examples.iter().enumerate() {
+            let path = format!("examples.{}", i);
+            if example.received.is_none() && example.time.is_none() {
+                let message = "example needs either received or time".to_string();
+                self.report(&path, message);
+            }
+            match &example.sender {
+                Some(sender)
+                    if !matcher.senders.is_empty() && !matcher.senders.contains(sender) =>
+                {
+                    let message = format!("example sender {} is not one of the senders", sender);
+                    self.report(&format!("{}.sender", path), message);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Path of the value an error is about, relative to the value being deserialized
+fn error_path(err: &serde_path_to_error::Error<serde_json::Error>) -> Vec<String> {
+    err.path()
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(index.to_string()),
+            Segment::Map { key } => Some(key.clone()),
+            Segment::Enum { .. } | Segment::Unknown => None,
+        })
+        .collect()
+}
+
+/// Leaves the value at `path` out of `value`, returning whether there was one
+fn leave_out(value: &mut serde_json::Value, path: &[String]) -> bool {
+    let (last, parent) = match path.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    let pointer: String = parent
+        .iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect();
+
+    match value.pointer_mut(&pointer) {
+        Some(serde_json::Value::Object(map)) if map.contains_key(last) => {
+            // rebuilt rather than removed from so that keys stay in order
+            *map = map
+                .iter()
+                .filter(|(key, _)| *key != last)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            true
+        }
+        Some(serde_json::Value::Array(items)) => match last.parse::<usize>() {
+            Ok(i) if i < items.len() => {
+                items.remove(i);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Deserializes `value`, found at `path` in the config, recording every mistake in it rather
+/// than just the first: the value each error is about is left out and deserializing tried
+/// again. What is returned despite mistakes is only good for further checks
+fn deserialize_all<T: DeserializeOwned>(
+    mut value: serde_json::Value,
+    path: &str,
+    matcher_id: Option<&str>,
+    locations: &Locations,
+    problems: &mut Vec<Problem>,
+) -> Option<T> {
+    let mut left_out: Vec<Vec<String>> = vec![];
+    loop {
+        let err = match serde_path_to_error::deserialize(&value) {
+            Ok(value) => return Some(value),
+            Err(err) => err,
+        };
+
+        // an error about what contains a value that was left out, like a required field being
+        // missing, was reported already
+        let error_path = error_path(&err);
+        if left_out
+            .iter()
+            .any(|p| p.len() > error_path.len() && p.starts_with(&error_path))
+        {
+            return None;
+        }
+
+        let full_path = error_path.iter().fold(path.to_string(), |path, segment| {
+            Locations::join(&path, segment)
+        });
+        problems.push(Problem {
+            location: locations.find(&full_path),
+            matcher_id: matcher_id.map(String::from),
+            message: err.into_inner().to_string(),
+        });
+
+        if !leave_out(&mut value, &error_path) {
+            return None;
+        }
+        left_out.push(error_path);
+    }
+}
+
+/// Checks that capture groups used by matchers exist in their patterns, matcher ids are unique,
+/// date formats are valid and examples say when they were received and come from the matcher's
+/// senders. Matchers are given with their position in the config
+fn check_matchers(
+    matchers: &[(usize, Matcher)],
+    locations: &Locations,
+    problems: &mut Vec<Problem>,
+) {
+    let mut ids = HashMap::new();
+
+    for (i, matcher) in matchers {
+        let mut check = MatcherCheck {
+            path: format!("matchers.{}", i),
+            id: &matcher.id,
+            groups: matcher.pattern.capture_names().flatten().collect(),
+            locations,
+            problems,
+        };
+
+        match ids.get(&matcher.id) {
+            Some(first) => {
+                let message = match locations.find(&format!("matchers.{}", first)) {
+                    Some((line, _)) => format!("id {} is also used on line {}", matcher.id, line),
+                    None => format!("id {} is used more than once", matcher.id),
+                };
+                check.report("id", message);
+            }
+            None => {
+                ids.insert(&matcher.id, i);
+            }
+        }
+
+        check.check(matcher);
+    }
+}
+
+fn check_budgets(budgets: &[Budget], locations: &Locations, problems: &mut Vec<Problem>) {
+    for (i, budget) in budgets.iter().enumerate() {
+        if let Period::Custom { days: 0, .. } = budget.period {
+            problems.push(Problem {
+                location: locations.find(&format!("budgets.{}.period.config.days", i)),
+                matcher_id: None,
+                message: "a custom budget period must be at least 1 day long".to_string(),
+            });
+        }
+    }
+}
+
+/// Deserializes and checks `config`, as read from `yaml`, listing every problem found. Each
+/// matcher is deserialized on its own and checked even if others have mistakes, and the parts
+/// of the config with mistakes in their types or values are left out to find more
+pub fn load(yaml: &str, mut config: serde_json::Value) -> Result<Config, Vec<Problem>> {
+    let locations = Locations::of(yaml);
+    let mut problems = vec![];
+
+    let matchers = match config.get_mut("matchers") {
+        Some(matchers) => std::mem::replace(matchers, serde_json::Value::Array(vec![])),
+        None => serde_json::Value::Null,
+    };
+    let rest: Option<Config> = deserialize_all(config, "", None, &locations, &mut problems);
+
+    let mut parsed = vec![];
+    match matchers {
+        serde_json::Value::Array(matchers) => {
+            for (i, matcher) in matchers.into_iter().enumerate() {
+                let id = matcher["id"].as_str().map(String::from);
+                let path = format!("matchers.{}", i);
+                if let Some(matcher) =
+                    deserialize_all(matcher, &path, id.as_deref(), &locations, &mut problems)
+                {
+                    parsed.push((i, matcher));
+                }
+            }
+        }
+        serde_json::Value::Null => {}
+        other => {
+            deserialize_all::<Vec<Matcher>>(other, "matchers", None, &locations, &mut problems);
+        }
+    }
+
+    check_matchers(&parsed, &locations, &mut problems);
+    if let Some(rest) = &rest {
+        check_budgets(&rest.budgets, &locations, &mut problems);
+    }
+
+    match rest {
+        Some(mut config) if problems.is_empty() => {
+            config.matchers = parsed.into_iter().map(|(_, matcher)| matcher).collect();
+            Ok(config)
+        }
+        _ => {
+            problems.sort_by_key(|p| p.location);
+            Err(problems)
+        }
+    }
+}
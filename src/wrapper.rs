@@ -1,7 +1,17 @@
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::Local;
+use chrono::LocalResult;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono_tz::Tz;
 use rusty_money::iso;
 use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Serialize;
 use std::ops::Deref;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 pub struct Currency(pub &'static iso::Currency);
@@ -10,7 +20,17 @@ impl Deref for Currency {
     type Target = iso::Currency;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.0
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        iso::find(s)
+            .map(Currency)
+            .ok_or(format!("currency not recognized: {}", s))
     }
 }
 
@@ -28,7 +48,7 @@ impl<'de> Visitor<'de> for CurrencyVisitor {
         E: serde::de::Error,
     {
         iso::find(v)
-            .map(|c| Currency(c))
+            .map(Currency)
             .ok_or(E::custom(format!("currency not recognized: {}", v)))
     }
 }
@@ -41,3 +61,128 @@ impl<'de> Deserialize<'de> for Currency {
         deserializer.deserialize_str(CurrencyVisitor)
     }
 }
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.iso_alpha_code)
+    }
+}
+
+/// Timezone that times in messages are written in
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Zone {
+    /// Timezone of the machine finny runs on
+    #[default]
+    Local,
+    /// An IANA timezone like `Asia/Karachi`
+    Named(Tz),
+    /// A fixed offset from UTC like `+05:00`
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    /// The UTC time of `naive` read in this timezone, the earlier one if it is ambiguous
+    pub fn to_utc(&self, naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        fn earliest<T: TimeZone>(time: LocalResult<DateTime<T>>) -> Option<DateTime<Utc>> {
+            match time {
+                LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                    Some(time.with_timezone(&Utc))
+                }
+                LocalResult::None => None,
+            }
+        }
+
+        match self {
+            Zone::Local => earliest(Local.from_local_datetime(naive)),
+            Zone::Named(tz) => earliest(tz.from_local_datetime(naive)),
+            Zone::Fixed(offset) => earliest(offset.from_local_datetime(naive)),
+        }
+    }
+
+    /// Wall clock time in this timezone at `time`
+    pub fn local(&self, time: &DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => time.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => time.with_timezone(tz).naive_local(),
+            Zone::Fixed(offset) => time.with_timezone(offset).naive_local(),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        if let Ok(tz) = s.parse::<Tz>() {
+            return Ok(Zone::Named(tz));
+        }
+
+        DateTime::parse_from_str(&format!("2000-01-01 00:00 {}", s), "%Y-%m-%d %H:%M %z")
+            .map(|time| Zone::Fixed(*time.offset()))
+            .map_err(|_| format!("timezone not recognized: {}", s))
+    }
+}
+
+struct ZoneVisitor;
+
+impl<'de> Visitor<'de> for ZoneVisitor {
+    type Value = Zone;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an IANA timezone, a UTC offset or Local")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        v.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Zone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ZoneVisitor)
+    }
+}
+
+/// Serializes `Money` as its decimal amount along with the ISO code of its currency, for use
+/// with `#[serde(with = "money_serde")]`
+pub mod money_serde {
+    use rust_decimal::Decimal;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    use super::Currency;
+    use crate::record::Money;
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedMoney {
+        amount: Decimal,
+        currency: Currency,
+    }
+
+    pub fn serialize<S: Serializer>(money: &Money, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedMoney {
+            amount: *money.amount(),
+            currency: Currency(money.currency()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        let money = SerializedMoney::deserialize(deserializer)?;
+        Ok(Money::from_decimal(money.amount, money.currency.0))
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::TimeZone;
+use chrono::Utc;
+use finny::chat_db::ChatDb;
+use finny::diagnostics::Diagnostics;
+use finny::diagnostics::Problem;
+use finny::message::MessageSource;
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/chat.db")
+}
+
+#[test]
+fn reads_fixture_database_without_modifying_it() {
+    let before = fs::read(fixture()).unwrap();
+    let mut diagnostics = Diagnostics::new();
+
+    let msgs = ChatDb::new(fixture())
+        .fetch(
+            &["8012".to_string()],
+            &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+    // message 3 has neither text nor an attributedBody and is skipped
+    assert_eq!(msgs.iter().map(|m| m.id).collect::<Vec<_>>(), vec![2, 1, 4]);
+    let unreadable: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| matches!(d.problem, Problem::Unreadable { .. }))
+        .map(|d| d.message_id)
+        .collect();
+    assert_eq!(unreadable, vec![3]);
+    assert!(msgs[0]
+        .text
+        .starts_with("Hello, your account 9 was charged"));
+    assert_eq!(msgs[1].sender, "8012");
+
+    assert_eq!(fs::read(fixture()).unwrap(), before);
+    assert!(!fixture().with_extension("db-journal").exists());
+    assert!(!fixture().with_extension("db-wal").exists());
+}
+
+#[test]
+fn only_reads_messages_from_given_contacts() {
+    let msgs = ChatDb::new(fixture())
+        .fetch(
+            &["9355".to_string()],
+            &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            &mut Diagnostics::new(),
+        )
+        .unwrap();
+
+    assert!(msgs.is_empty());
+}
@@ -0,0 +1,150 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use finny::config;
+use finny::config::Config;
+
+/// Problems found in the example config after making each `(from, to)` replacement, as
+/// displayed, along with the line each `to` starts on
+fn problems(name: &str, replacements: &[(&str, &str)]) -> (Vec<String>, Vec<usize>) {
+    let example = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("example.config.yml");
+    let mut yaml = fs::read_to_string(example).unwrap();
+    let mut lines = vec![];
+    for (from, to) in replacements {
+        match yaml.find(from) {
+            Some(i) => lines.push(yaml[..i].lines().count() + 1),
+            None => panic!("{} is not in the example config", from),
+        }
+        yaml = yaml.replacen(from, to, 1);
+    }
+
+    let path = env::temp_dir().join(format!("finny-{}-{}.yml", name, std::process::id()));
+    fs::write(&path, yaml).unwrap();
+    let result = Config::new(&path.to_string_lossy());
+    fs::remove_file(&path).unwrap();
+
+    let problems = match result {
+        Err(config::Error::Invalid(problems)) => problems.iter().map(|p| p.to_string()).collect(),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => vec![],
+    };
+    (problems, lines)
+}
+
+#[test]
+fn example_config_is_valid() {
+    assert!(problems("valid", &[]).0.is_empty());
+}
+
+#[test]
+fn locates_values_of_the_wrong_type() {
+    let (problems, lines) = problems("priority", &[("    # priority: 1", "    priority: high")]);
+    assert_eq!(
+        problems,
+        vec![format!(
+            "line {}, column 5: matcher pos-purchase-bank-a: invalid type: string \"high\", \
+             expected i32",
+            lines[0]
+        )]
+    );
+}
+
+#[test]
+fn locates_values_inside_tagged_enums() {
+    let (problems, lines) = problems(
+        "default",
+        &[(
+            "          # default: Unknown",
+            "          default: [Unknown]",
+        )],
+    );
+    let location = format!("line {}, column 11: matcher pos-purchase-bank-a", lines[0]);
+    assert_eq!(problems.len(), 1);
+    assert!(
+        problems[0].starts_with(&format!("{}: invalid type", location)),
+        "{}",
+        problems[0]
+    );
+}
+
+#[test]
+fn locates_missing_groups() {
+    let (problems, lines) = problems(
+        "group",
+        &[("          group: amount", "          group: amt")],
+    );
+    assert_eq!(
+        problems,
+        vec![format!(
+            "line {}, column 11: matcher pos-purchase-bank-a: group amt is not in the pattern",
+            lines[0]
+        )]
+    );
+}
+
+#[test]
+fn lists_every_problem_at_once() {
+    let (problems, lines) = problems(
+        "every",
+        &[
+            ("      type: Monthly", "      type: Yearly"),
+            ("    # priority: 1", "    priority: high"),
+            ("    # senders: ['8012']", "    senders: 8012"),
+            ("          group: amount", "          group: amt"),
+        ],
+    );
+    assert_eq!(
+        problems,
+        vec![
+            format!(
+                "line {}, column 7: unknown variant `Yearly`, expected one of `Monthly`, \
+                 `Weekly`, `Custom`",
+                lines[0]
+            ),
+            format!(
+                "line {}, column 5: matcher pos-purchase-bank-a: invalid type: string \"high\", \
+                 expected i32",
+                lines[1]
+            ),
+            format!(
+                "line {}, column 5: matcher pos-purchase-bank-a: invalid type: integer `8012`, \
+                 expected a sequence",
+                lines[2]
+            ),
+            format!(
+                "line {}, column 11: matcher pos-purchase-bank-a: group amt is not in the pattern",
+                lines[3]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn explains_mistakes_in_natures() {
+    let (problems, lines) = problems(
+        "nature",
+        &[
+            ("    nature: Debit # or", "    nature: Debt # or"),
+            (
+                "          refunded: Reversal",
+                "          refunded: Refunded",
+            ),
+        ],
+    );
+    assert_eq!(
+        problems,
+        vec![
+            format!(
+                "line {}, column 5: matcher pos-purchase-bank-a: unknown variant `Debt`, \
+                 expected one of `Credit`, `Debit`, `Refund`, `Reversal`",
+                lines[0]
+            ),
+            format!(
+                "line {}, column 11: matcher online-purchase-bank-b: unknown variant `Refunded`, \
+                 expected one of `Credit`, `Debit`, `Refund`, `Reversal`",
+                lines[1]
+            ),
+        ]
+    );
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use chrono::TimeZone;
+use chrono::Utc;
+use finny::diagnostics::Diagnostics;
+use finny::diagnostics::Problem;
+use finny::export::Export;
+use finny::export::ExportFormat;
+use finny::export::FieldMapping;
+use finny::message::MessageSource;
+use finny::message::TextMessage;
+
+fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn csv_mapping() -> FieldMapping {
+    FieldMapping {
+        sender: "From".to_string(),
+        body: "Message".to_string(),
+        time: "Received".to_string(),
+        time_format: Some("%d/%m/%Y %H:%M".to_string()),
+        id: Some("Ref".to_string()),
+    }
+}
+
+fn fetch(export: Export, diagnostics: &mut Diagnostics) -> Vec<TextMessage> {
+    export
+        .fetch(
+            &["8012".to_string()],
+            &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            diagnostics,
+        )
+        .unwrap()
+}
+
+fn reasons(diagnostics: &Diagnostics) -> Vec<(u32, &str)> {
+    diagnostics
+        .iter()
+        .map(|d| match &d.problem {
+            Problem::Unreadable { reason } => (d.message_id, reason.as_str()),
+            other => panic!("unexpected problem {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn reads_mapped_csv_columns() {
+    let mut diagnostics = Diagnostics::new();
+    let export = Export::new(&fixture("messages.csv"), ExportFormat::Csv, csv_mapping());
+    let msgs = fetch(export, &mut diagnostics);
+
+    assert_eq!(
+        msgs.iter()
+            .map(|m| (m.id, m.sender.as_str(), m.text.as_str(), m.time))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                7,
+                "8012",
+                "Your account 1234 was debited with 1,500.00",
+                Utc.ymd(2026, 10, 1).and_hms(9, 30, 0)
+            ),
+            (
+                9,
+                "8012",
+                "Your account 1234 was credited with 50.00",
+                Utc.ymd(2026, 10, 2).and_hms(18, 45, 0)
+            ),
+        ]
+    );
+
+    // the row from another contact is skipped even though its time can't be read
+    let reasons = reasons(&diagnostics);
+    assert_eq!(reasons.len(), 2);
+    assert_eq!(reasons[0].0, 11);
+    assert!(reasons[0].1.starts_with("line 6: invalid message time"));
+    assert_eq!(reasons[1].1, "line 7: invalid message id: twelve");
+}
+
+#[test]
+fn reads_json_lines_and_skips_bad_lines() {
+    let mut diagnostics = Diagnostics::new();
+    let export = Export::new(
+        &fixture("messages.jsonl"),
+        ExportFormat::JsonLines,
+        FieldMapping::default(),
+    );
+    let msgs = fetch(export, &mut diagnostics);
+
+    assert_eq!(
+        msgs.iter()
+            .map(|m| (m.text.as_str(), m.time))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "Your account 1234 was debited with 1,500.00",
+                Utc.ymd(2026, 10, 1).and_hms(4, 30, 0)
+            ),
+            (
+                "Your account 1234 was credited with 50.00",
+                Utc.ymd(2026, 10, 2).and_hms(18, 45, 0)
+            ),
+            ("A numeric sender", Utc.ymd(2026, 10, 3).and_hms(8, 0, 0)),
+        ]
+    );
+
+    let reasons = reasons(&diagnostics);
+    assert_eq!(reasons.len(), 1);
+    assert_eq!(reasons[0].0, 5);
+    assert!(reasons[0].1.starts_with("line 5: invalid JSON"));
+}
+
+#[test]
+fn ids_depend_on_the_message_not_its_position() {
+    let export = Export::new(
+        &fixture("messages.jsonl"),
+        ExportFormat::JsonLines,
+        FieldMapping::default(),
+    );
+    let msgs = fetch(export, &mut Diagnostics::new());
+
+    assert_eq!(
+        msgs[1].id,
+        finny::message::stable_id(&[
+            "8012",
+            "2026-10-02T18:45:00Z",
+            "Your account 1234 was credited with 50.00"
+        ])
+    );
+    assert_ne!(msgs[0].id, msgs[1].id);
+}
+
+#[test]
+fn filters_by_window() {
+    let export = Export::new(&fixture("messages.csv"), ExportFormat::Csv, csv_mapping());
+    let msgs = export
+        .fetch(
+            &["8012".to_string()],
+            &Utc.ymd(2026, 10, 2).and_hms(0, 0, 0),
+            &Utc.ymd(2026, 10, 3).and_hms(0, 0, 0),
+            &mut Diagnostics::new(),
+        )
+        .unwrap();
+
+    assert_eq!(msgs.iter().map(|m| m.id).collect::<Vec<_>>(), vec![9]);
+}
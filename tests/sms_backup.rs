@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use chrono::TimeZone;
+use chrono::Utc;
+use finny::diagnostics::Diagnostics;
+use finny::diagnostics::Problem;
+use finny::message::MessageSource;
+use finny::sms_backup::SmsBackup;
+
+fn fixture() -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/sms.xml")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn skips_sent_and_unreadable_messages() {
+    let mut diagnostics = Diagnostics::new();
+    let msgs = SmsBackup::new(&fixture())
+        .fetch(
+            &["8012".to_string()],
+            &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+    assert_eq!(
+        msgs.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(),
+        vec![
+            "Hello, your account 1234 was charged at 01-10-26 10:00 for 1,000.00 by Café & Co",
+            "Your account 1234 was credited with 50.00",
+        ]
+    );
+
+    let reasons: Vec<_> = diagnostics
+        .iter()
+        .map(|d| match &d.problem {
+            Problem::Unreadable { reason } => reason.as_str(),
+            other => panic!("unexpected problem {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        reasons,
+        vec!["sms without body", "invalid sms date: 99999999999999999"]
+    );
+}
+
+#[test]
+fn ids_depend_on_the_message_not_its_position() {
+    let msgs = SmsBackup::new(&fixture())
+        .fetch(
+            &["8012".to_string()],
+            &Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            &mut Diagnostics::new(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        msgs[1].id,
+        finny::message::stable_id(&[
+            "8012",
+            "1791779482000",
+            "Your account 1234 was credited with 50.00"
+        ])
+    );
+    assert_ne!(msgs[0].id, msgs[1].id);
+}